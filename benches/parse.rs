@@ -0,0 +1,89 @@
+//! Timings for the CPU-bound work done on each update:
+//! parsing menu layouts and scaling icon pixmaps.
+//!
+//! Run with `cargo bench`, and compare the output between changes.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use system_tray::item::{IconPixmap, ScaleQuality};
+use system_tray::menu::MenuItem;
+use zbus::zvariant::{OwnedValue, Value};
+
+/// How long each case runs for.
+const RUN_TIME: Duration = Duration::from_secs(1);
+
+fn main() {
+    for (breadth, depth) in [(10, 1), (100, 1), (10, 3)] {
+        let mut next_id = 1;
+        let menu = make_menu(&mut next_id, breadth, depth);
+        let menu = OwnedValue::try_from(menu).expect("value should convert");
+
+        bench(&format!("parse menu ({} items)", next_id - 1), || {
+            MenuItem::try_from(&menu).expect("menu should parse")
+        });
+    }
+
+    for size in [22, 64, 256] {
+        let pixmap = make_pixmap(size);
+
+        bench(&format!("scale pixmap fast ({size}px to 24px)"), || {
+            pixmap.scaled(24, ScaleQuality::Fast)
+        });
+        bench(&format!("scale pixmap smooth ({size}px to 24px)"), || {
+            pixmap.scaled(24, ScaleQuality::Smooth)
+        });
+    }
+}
+
+/// Runs `f` repeatedly for [`RUN_TIME`], printing the average time per run.
+fn bench<R>(name: &str, mut f: impl FnMut() -> R) {
+    let started = Instant::now();
+    let mut runs = 0u32;
+
+    while started.elapsed() < RUN_TIME {
+        black_box(f());
+        runs += 1;
+    }
+
+    println!("{name:<40} {:>12.2?}", started.elapsed() / runs);
+}
+
+/// Builds a menu item with `breadth` children at each of `depth` levels below it.
+fn make_menu(next_id: &mut i32, breadth: usize, depth: usize) -> Value<'static> {
+    let id = *next_id;
+    *next_id += 1;
+
+    let mut properties: HashMap<&str, Value> = HashMap::from([
+        ("label", Value::from("_Item")),
+        ("icon-name", Value::from("document-open")),
+        ("enabled", Value::from(true)),
+    ]);
+
+    let children: Vec<Value> = if depth == 0 {
+        vec![]
+    } else {
+        properties.insert("children-display", Value::from("submenu"));
+        (0..breadth)
+            .map(|_| Value::Value(Box::new(make_menu(next_id, breadth, depth - 1))))
+            .collect()
+    };
+
+    Value::from((id, properties, children))
+}
+
+/// Builds an opaque, square pixmap with a gradient, `size` pixels across.
+fn make_pixmap(size: i32) -> IconPixmap {
+    let pixels = (0..size * size)
+        .flat_map(|index| {
+            let [.., x, y] = index.to_be_bytes();
+            [0xff, x, y, x ^ y]
+        })
+        .collect();
+
+    IconPixmap {
+        width: size,
+        height: size,
+        pixels,
+    }
+}
@@ -1,25 +1,42 @@
+use crate::clock::{self, Clock, SystemClock, Ticker};
 use crate::dbus::dbus_menu_proxy::{DBusMenuProxy, PropertiesUpdate};
 use crate::dbus::notifier_item_proxy::StatusNotifierItemProxy;
 use crate::dbus::notifier_watcher_proxy::StatusNotifierWatcherProxy;
 use crate::dbus::status_notifier_watcher::StatusNotifierWatcher;
 use crate::dbus::{self, OwnedValueExt};
 use crate::error::{Error, Result};
-use crate::item::{self, Status, StatusNotifierItem, Tooltip};
-use crate::menu::{MenuDiff, TrayMenu};
+use crate::fixture::Recorder;
+use crate::item::{self, sanitize_text, Icon, IconPixmap, Status, StatusNotifierItem, Tooltip};
+use crate::menu::{self, MenuDiff, MenuItem, MenuStatus, TextDirection, TrayMenu};
 use crate::names;
+use crate::state::{self, State};
+use crate::sync::lock;
 use dbus::DBusProps;
-use futures_lite::StreamExt;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use futures_lite::future::poll_once;
+use futures_lite::{FutureExt, Stream, StreamExt};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::hash::{BuildHasher, DefaultHasher, Hash, Hasher, RandomState};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::spawn;
-use tokio::sync::broadcast;
-use tokio::time::timeout;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, OnceCell};
+use tokio::task::{AbortHandle, JoinSet};
+use tokio::time::Instant;
 use tracing::{debug, error, trace, warn};
-use zbus::fdo::{DBusProxy, PropertiesProxy};
-use zbus::names::InterfaceName;
-use zbus::zvariant::{Structure, Value};
-use zbus::{Connection, Message};
+use zbus::fdo::{
+    DBusProxy, IntrospectableProxy, NameOwnerChangedStream, PeerProxy, PropertiesChangedStream,
+    PropertiesProxy,
+};
+use zbus::names::{BusName, InterfaceName};
+use zbus::proxy::{CacheProperties, SignalStream};
+use zbus::zvariant::{ObjectPath, OwnedValue, Str, Structure, Value};
+use zbus::{Connection, MatchRule, Message, MessageStream};
 
 use self::names::ITEM_OBJECT;
 
@@ -36,6 +53,129 @@ pub enum Event {
     Update(String, UpdateEvent),
     /// A `StatusNotifierItem` was unregistered.
     Remove(String),
+    /// A problem was encountered with an item which did not prevent it from being handled.
+    Warning(String, Warning),
+    /// The client was restarted using [`Client::restart`],
+    /// and has finished loading the items currently registered.
+    ///
+    /// This contains every item and its address.
+    /// Any previously known items which are not included are gone.
+    Reset(Vec<(String, StatusNotifierItem)>),
+    /// The connection to the bus was lost, for example because the bus daemon restarted,
+    /// and the client has reconnected and finished loading the items now registered.
+    ///
+    /// As with [`Event::Reset`], this contains every item and its address,
+    /// and any previously known items which are not included are gone.
+    Reconnected(Vec<(String, StatusNotifierItem)>),
+}
+
+/// A non-fatal problem encountered while handling an item.
+#[derive(Debug, Clone)]
+pub enum Warning {
+    /// A property did not have the expected type, and was skipped.
+    /// The rest of the item or update was still processed.
+    InvalidProperty {
+        /// The name of the `StatusNotifierItem` property.
+        name: String,
+        /// A description of the parsing error.
+        message: String,
+    },
+    /// A call to the item kept failing after being retried,
+    /// so the change it was fetching was lost.
+    ///
+    /// See [`ClientBuilder::retry_policy`].
+    CallFailed {
+        /// The D-Bus method, along with the property for `Get`.
+        method: String,
+        /// A description of the last error.
+        message: String,
+    },
+    /// A background task for the item returned an error or panicked.
+    ///
+    /// The failure only affects this item.
+    /// If the task was watching the item, the item is removed after this warning,
+    /// and if it was watching the menu, the menu is no longer kept up to date.
+    TaskFailed {
+        /// A description of the error or panic.
+        message: String,
+    },
+    /// A call to the item is taking longer than the slow call threshold.
+    /// The call is still in flight when this is sent.
+    ///
+    /// See [`ClientBuilder::slow_call_threshold`].
+    SlowCall {
+        /// The D-Bus method, along with the property for `Get`.
+        method: String,
+        /// How long the call had been in flight.
+        elapsed: Duration,
+    },
+}
+
+/// A handler with one method for each [`Event`] variant,
+/// for use with [`Event::dispatch`].
+///
+/// Every method is required,
+/// so implementations fail to compile if a variant is left unhandled.
+///
+/// ```
+/// use system_tray::client::{EventHandler, UpdateEvent, Warning};
+/// use system_tray::item::StatusNotifierItem;
+///
+/// struct Logger;
+///
+/// impl EventHandler for Logger {
+///     fn add(&mut self, address: String, item: Box<StatusNotifierItem>) {
+///         println!("added {address}: {}", item.id);
+///     }
+///
+///     fn update(&mut self, address: String, update: UpdateEvent) {
+///         println!("updated {address}: {update:?}");
+///     }
+///
+///     fn remove(&mut self, address: String) {
+///         println!("removed {address}");
+///     }
+///
+///     fn warning(&mut self, address: String, warning: Warning) {
+///         println!("warning for {address}: {warning:?}");
+///     }
+///
+///     fn reset(&mut self, items: Vec<(String, StatusNotifierItem)>) {
+///         println!("reset with {} items", items.len());
+///     }
+///
+///     fn reconnected(&mut self, items: Vec<(String, StatusNotifierItem)>) {
+///         println!("reconnected with {} items", items.len());
+///     }
+/// }
+/// ```
+pub trait EventHandler {
+    /// Handles [`Event::Add`].
+    fn add(&mut self, address: String, item: Box<StatusNotifierItem>);
+    /// Handles [`Event::Update`].
+    fn update(&mut self, address: String, update: UpdateEvent);
+    /// Handles [`Event::Remove`].
+    fn remove(&mut self, address: String);
+    /// Handles [`Event::Warning`].
+    fn warning(&mut self, address: String, warning: Warning);
+    /// Handles [`Event::Reset`].
+    fn reset(&mut self, items: Vec<(String, StatusNotifierItem)>);
+    /// Handles [`Event::Reconnected`].
+    fn reconnected(&mut self, items: Vec<(String, StatusNotifierItem)>);
+}
+
+impl Event {
+    /// Passes the event to the method on `handler` matching its variant.
+    pub fn dispatch<H: EventHandler + ?Sized>(self, handler: &mut H) {
+        match self {
+            Event::Add(address, item) => handler.add(address, item),
+            Event::Update(address, update) => handler.update(address, update),
+            Event::Remove(address) => handler.remove(address),
+            Event::Warning(address, warning) => handler.warning(address, warning),
+            Event::Reset(items) => handler.reset(items),
+            Event::Reconnected(items) => handler.reconnected(items),
+        }
+    }
 }
 
 /// The specific change associated with an update event.
@@ -47,15 +187,37 @@ pub enum UpdateEvent {
     Status(Status),
     Title(Option<String>),
     Tooltip(Option<Tooltip>),
+    /// The pixmap of the main icon was loaded, or has changed.
+    IconPixmap(Option<Vec<IconPixmap>>),
+    /// The pixmap of the overlay icon was loaded, or has changed.
+    OverlayIconPixmap(Option<Vec<IconPixmap>>),
+    /// The pixmap of the attention icon was loaded, or has changed.
+    AttentionIconPixmap(Option<Vec<IconPixmap>>),
     /// A menu layout has changed.
     /// The entire layout is sent.
     Menu(TrayMenu),
     /// One or more menu properties have changed.
-    /// Only the updated properties are sent.
+    /// Only the updated properties are sent,
+    /// and the cached menu is updated with them.
+    /// See [`TrayMenu::apply_diffs`].
     MenuDiff(Vec<MenuDiff>),
     /// A new menu has connected to the item.
     /// Its name on bus is sent.
+    ///
+    /// This is also sent when the item's menu moves to a new object path.
+    /// The previous layout no longer applies,
+    /// and if menus are tracked the new one follows as an [`UpdateEvent::Menu`].
     MenuConnect(String),
+    /// The root menu was closed using [`Client::close_menu`],
+    /// completing the interaction cycle started by [`Client::open_menu`].
+    ///
+    /// Applications may rebuild their menu in response to it closing.
+    /// If menus are tracked, the new layout follows as a separate [`UpdateEvent::Menu`].
+    MenuClosed,
+    /// The status of the menu has changed.
+    MenuStatus(MenuStatus),
+    /// The direction of the text in the menu has changed.
+    MenuTextDirection(TextDirection),
 }
 
 /// A request to 'activate' one of the menu items,
@@ -76,481 +238,3134 @@ pub enum ActivateRequest {
     Secondary { address: String, x: i32, y: i32 },
 }
 
-type State = HashMap<String, (StatusNotifierItem, Option<TrayMenu>)>;
+/// The on-screen area of an item's icon, in screen coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
 
-const PROPERTIES_INTERFACE: &str = "org.kde.StatusNotifierItem";
+impl Rect {
+    /// Gets the point at the center of the bottom edge,
+    /// which suits popups opened from a panel at the top of the screen.
+    #[must_use]
+    pub fn anchor(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height)
+    }
+}
 
-/// Client for watching the tray.
-#[derive(Debug)]
-pub struct Client {
-    tx: broadcast::Sender<Event>,
-    _rx: broadcast::Receiver<Event>,
-    connection: Connection,
+/// A snapshot of what the client is doing, from [`Client::stats`],
+/// for finding out which items keep it busy.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// The number of items in the tray.
+    pub items: usize,
+    /// The number of items still loading.
+    pub pending_items: usize,
+    /// The number of background tasks running.
+    pub tasks: usize,
+    /// The activity of each item, keyed on its address.
+    pub item_stats: HashMap<String, ItemStats>,
+}
 
-    items: Arc<Mutex<State>>,
+/// The activity of a single item and its menu.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ItemStats {
+    /// The number of signals received from the item and its menu.
+    pub signals: u64,
+    /// How long the client took to handle the latest signals.
+    pub last_handled: Option<Duration>,
 }
 
-impl Client {
-    /// Creates and initializes the client.
+/// What an item and its menu were found to implement by introspecting them,
+/// from [`Client::capabilities`].
+///
+/// Items are only introspected if enabled with [`ClientBuilder::introspect_items`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The `StatusNotifierItem` interface the item implements,
+    /// such as `org.kde.StatusNotifierItem` or `org.freedesktop.StatusNotifierItem`.
+    pub interface: Option<String>,
+    /// Whether the object at the item's menu path implements `com.canonical.dbusmenu`.
+    pub menu: bool,
+    /// The methods of the item's interface.
+    pub methods: Vec<String>,
+    /// The signals of the item's interface.
+    pub signals: Vec<String>,
+    /// The methods of the menu interface.
+    pub menu_methods: Vec<String>,
+}
+
+impl Capabilities {
+    /// Gets whether the item implements `method`, such as `ContextMenu`.
+    #[must_use]
+    pub fn has_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|name| name == method)
+    }
+
+    /// Gets whether the menu implements `method`, such as `AboutToShowGroup`.
+    #[must_use]
+    pub fn has_menu_method(&self, method: &str) -> bool {
+        self.menu_methods.iter().any(|name| name == method)
+    }
+}
+
+/// How calls to items are retried when they fail for a reason which is likely to pass,
+/// such as the application starting up or being busy,
+/// set using [`ClientBuilder::retry_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The most times a call is made, including the first.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry, doubling for each one after.
+    pub initial_delay: Duration,
+    /// The longest wait between retries.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, waiting 100ms then 200ms.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy which never retries.
+    #[must_use]
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Gets how long to wait before retrying after `attempt` attempts.
     ///
-    /// The client will begin listening to items and menus and sending events immediately.
-    /// It is recommended that consumers immediately follow the call to `new` with a `subscribe` call,
-    /// then immediately follow that with a call to `items` to get the state to not miss any events.
+    /// A random quarter of the delay is taken off,
+    /// so that calls failing together are not all retried together.
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+        delay - delay / 4 * u32::try_from(jitter).unwrap_or_default() / 1000
+    }
+}
+
+/// What an [`EventReceiver`] does when it falls so far behind
+/// that the oldest events it has not received are dropped,
+/// set using [`ClientBuilder::lag_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LagPolicy {
+    /// Return [`RecvError::Lagged`] with the number of events missed.
+    #[default]
+    Error,
+    /// Skip to the latest event, returning [`Event::Reset`] with the current items
+    /// to replace any state built from the missed events.
+    Resync,
+}
+
+/// A subscription to the events of a client, from [`Client::events`],
+/// which handles falling behind according to the [`LagPolicy`].
+#[derive(Debug)]
+pub struct EventReceiver {
+    rx: broadcast::Receiver<Event>,
+    items: Arc<Mutex<State>>,
+    lag_policy: LagPolicy,
+}
+
+impl EventReceiver {
+    /// Waits for the next event.
     ///
-    /// The value of `service_name` must be unique on the session bus.
-    /// It is recommended to use something similar to the format of `appid-numid`,
-    /// where `numid` is a short-ish random integer.
+    /// With [`LagPolicy::Resync`], missing events returns [`Event::Reset`] instead of an error.
+    /// Its items have no menus, so with [`TrayState`](crate::state::TrayState)
+    /// the menus from before the missed events are kept.
     ///
     /// # Errors
     ///
-    /// If the initialization fails for any reason,
-    /// for example if unable to connect to the bus,
-    /// this method will return an error.
-    ///
-    /// # Panics
-    ///
-    /// If the generated well-known name is invalid, the library will panic
-    /// as this indicates a major bug.
-    ///
-    /// Likewise, the spawned tasks may panic if they cannot get a `Mutex` lock.
-    pub async fn new() -> Result<Self> {
-        let connection = Connection::session().await?;
-        let (tx, rx) = broadcast::channel(32);
+    /// Returns [`RecvError::Closed`] once the client is dropped,
+    /// or [`RecvError::Lagged`] if events were missed with [`LagPolicy::Error`].
+    pub async fn recv(&mut self) -> std::result::Result<Event, RecvError> {
+        match self.rx.recv().await {
+            Err(RecvError::Lagged(missed)) if self.lag_policy == LagPolicy::Resync => {
+                warn!("subscriber missed {missed} events, resyncing");
 
-        // first start server...
-        StatusNotifierWatcher::new().attach_to(&connection).await?;
+                // events sent from here on are newer than the snapshot,
+                // or are already part of it, which applies the same again
+                self.rx = self.rx.resubscribe();
+                Ok(Event::Reset(snapshot(&self.items)))
+            }
+            result => result,
+        }
+    }
+}
 
-        // ...then connect to it
-        let watcher_proxy = StatusNotifierWatcherProxy::new(&connection).await?;
+const PROPERTIES_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
 
-        // register a host on the watcher to declare we want to watch items
-        // get a well-known name
-        let pid = std::process::id();
-        let mut i = 0;
-        let wellknown = loop {
-            use zbus::fdo::RequestNameReply::*;
+/// The tracing target for raw messages from the item set with [`ClientBuilder::debug_destination`].
+const RAW_TARGET: &str = "system_tray::raw";
 
-            i += 1;
-            let wellknown = format!("org.freedesktop.StatusNotifierHost-{pid}-{i}");
-            let wellknown: zbus::names::WellKnownName = wellknown
-                .try_into()
-                .expect("generated well-known name is invalid");
+/// The properties of an item, except for the icon pixmaps.
+const NON_PIXMAP_PROPERTIES: &[&str] = &[
+    "Id",
+    "Category",
+    "Title",
+    "Status",
+    "WindowId",
+    "IconThemePath",
+    "IconName",
+    "OverlayIconName",
+    "AttentionIconName",
+    "AttentionMovieName",
+    "ToolTip",
+    "ItemIsMenu",
+    "Menu",
+];
 
-            let flags = [zbus::fdo::RequestNameFlags::DoNotQueue];
-            match connection
-                .request_name_with_flags(&wellknown, flags.into_iter().collect())
-                .await?
-            {
-                PrimaryOwner => break wellknown,
-                Exists | AlreadyOwner => {}
-                InQueue => unreachable!(
-                    "request_name_with_flags returned InQueue even though we specified DoNotQueue"
-                ),
-            };
-        };
+/// The icon pixmap properties of an item.
+const PIXMAP_PROPERTIES: &[&str] = &["IconPixmap", "OverlayIconPixmap", "AttentionIconPixmap"];
 
-        debug!("wellknown: {wellknown}");
-        watcher_proxy
-            .register_status_notifier_host(&wellknown)
-            .await?;
+/// The menu item properties requested in layouts, apart from `icon-data`.
+///
+/// Requesting them explicitly rather than all properties
+/// skips any the client does not use.
+const MENU_PROPERTIES: &[&str] = &[
+    "type",
+    "label",
+    "enabled",
+    "visible",
+    "icon-name",
+    "shortcut",
+    "toggle-type",
+    "toggle-state",
+    "children-display",
+    "disposition",
+];
 
-        let items = Arc::new(Mutex::new(HashMap::new()));
+/// The first dbusmenu protocol version with the batched `*Group` methods.
+const MENU_GROUP_VERSION: u32 = 3;
 
-        // handle new items
-        {
-            let connection = connection.clone();
-            let tx = tx.clone();
-            let items = items.clone();
+/// How long to wait after a menu layout update for any more before fetching it.
+const LAYOUT_COALESCE_WINDOW: Duration = Duration::from_millis(50);
 
-            let mut stream = watcher_proxy
-                .receive_status_notifier_item_registered()
-                .await?;
+/// The most buffered signals taken from a stream at once.
+///
+/// An application sending signals faster than they are handled
+/// would otherwise keep its task draining the stream,
+/// never getting to its other signals, such as the disconnect.
+const MAX_SIGNAL_BATCH: usize = 64;
 
-            spawn(async move {
-                while let Some(item) = stream.next().await {
-                    let address = item.args().map(|args| args.service);
+/// The most items loaded at once when the client starts,
+/// so that startup takes as long as the slowest item rather than all of them together.
+const MAX_CONCURRENT_LOADS: usize = 8;
 
-                    if let Ok(address) = address {
-                        debug!("received new item: {address}");
-                        if let Err(err) = Self::handle_item(
-                            address,
-                            connection.clone(),
-                            tx.clone(),
-                            items.clone(),
-                        )
-                        .await
-                        {
-                            error!("{err}");
-                            break;
-                        }
-                    }
-                }
+/// How long an item can take to load before a warning is logged, in debug builds.
+const SLOW_LOAD_THRESHOLD: Duration = Duration::from_secs(10);
 
-                Ok::<(), Error>(())
-            });
-        }
+/// How long an item has to reply to a liveness check.
+const LIVENESS_TIMEOUT: Duration = Duration::from_secs(5);
 
-        // then lastly get all items
-        // it can take so long to fetch all items that we have to do this last,
-        // otherwise some incoming items get missed
-        {
-            let connection = connection.clone();
-            let tx = tx.clone();
-            let items = items.clone();
-
-            spawn(async move {
-                let initial_items = watcher_proxy.registered_status_notifier_items().await?;
-                debug!("initial items: {initial_items:?}");
-
-                for item in initial_items {
-                    if let Err(err) =
-                        Self::handle_item(&item, connection.clone(), tx.clone(), items.clone())
-                            .await
-                    {
-                        error!("{err}");
-                    }
-                }
+/// How attempts to reconnect to the bus are spaced out after the connection is lost.
+/// Attempts carry on until one succeeds.
+const RECONNECT_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: u32::MAX,
+    initial_delay: Duration::from_millis(500),
+    max_delay: Duration::from_secs(30),
+};
 
-                Ok::<(), Error>(())
-            });
+/// Client for watching the tray.
+#[derive(Debug)]
+pub struct Client {
+    ctx: Arc<Mutex<Context>>,
+    /// The task reconnecting to the bus when the connection is lost.
+    reconnect_task: AbortHandle,
+    _rx: broadcast::Receiver<Event>,
+}
+
+/// Options controlling the behaviour of a [`Client`].
+#[derive(Debug, Clone)]
+struct Config {
+    track_menus: bool,
+    prefetch_menus: bool,
+    expected_items: usize,
+    liveness_interval: Option<Duration>,
+    idle_ping_interval: Option<Duration>,
+    poll_interval: Option<Duration>,
+    debug_destination: Option<String>,
+    on_event: Option<EventCallback>,
+    item_configs: HashMap<String, ItemConfig>,
+    lazy_pixmaps: bool,
+    visible_menu_items_only: bool,
+    max_menu_depth: usize,
+    menu_layout_depth: i32,
+    menu_icon_data: bool,
+    about_to_show_cooldown: Duration,
+    prefetch_submenus: bool,
+    channel_capacity: usize,
+    lag_policy: LagPolicy,
+    retry_policy: RetryPolicy,
+    call_timeout: Duration,
+    slow_call_threshold: Option<Duration>,
+    max_text_length: usize,
+    introspect_items: bool,
+    bus_address: Option<String>,
+    capture: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Config {
+    /// Gets the menu item properties to request in layouts.
+    fn menu_properties(&self) -> Vec<&'static str> {
+        let mut properties = MENU_PROPERTIES.to_vec();
+        if self.menu_icon_data {
+            properties.push("icon-data");
         }
+        properties
+    }
 
-        // Handle other watchers unregistering and this one taking over
-        // It is necessary to clear all items as our watcher will then re-send them all
-        {
-            let tx = tx.clone();
-            let items = items.clone();
+    /// Gets whether to swap the pixmap byte order for the application with `id`,
+    /// or `None` to detect it.
+    fn swap_pixmap_byte_order(&self, id: &str) -> Option<bool> {
+        self.item_configs
+            .get(id)
+            .and_then(|config| config.swap_pixmap_byte_order)
+    }
 
-            let dbus_proxy = DBusProxy::new(&connection).await?;
+    /// Gets how long the application with `id` has to reply to a call.
+    fn call_timeout(&self, id: &str) -> Duration {
+        self.item_configs
+            .get(id)
+            .and_then(|config| config.call_timeout)
+            .unwrap_or(self.call_timeout)
+    }
+}
 
-            let mut stream = dbus_proxy.receive_name_acquired().await?;
+/// Options overriding the behaviour of a [`Client`] for a single application,
+/// set using [`ClientBuilder::item_config`].
+#[derive(Debug, Clone, Default)]
+pub struct ItemConfig {
+    swap_pixmap_byte_order: Option<bool>,
+    call_timeout: Option<Duration>,
+}
 
-            spawn(async move {
-                while let Some(thing) = stream.next().await {
-                    let body = thing.args()?;
-                    if body.name == names::WATCHER_BUS {
-                        let mut items = items.lock().expect("mutex lock should succeed");
-                        let keys = items.keys().cloned().collect::<Vec<_>>();
-                        for address in keys {
-                            items.remove(&address);
-                            tx.send(Event::Remove(address))?;
-                        }
-                    }
-                }
+impl ItemConfig {
+    /// Creates a config which does not override anything.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-                Ok::<(), Error>(())
-            });
-        }
+    /// Sets whether to reverse the byte order of the application's icon pixmaps,
+    /// for applications which send them in little-endian order instead of network order.
+    ///
+    /// By default, the bytes are only swapped
+    /// if the alpha channel looks implausible in network order.
+    #[must_use]
+    pub fn swap_pixmap_byte_order(mut self, swap: bool) -> Self {
+        self.swap_pixmap_byte_order = Some(swap);
+        self
+    }
 
-        debug!("tray client initialized");
+    /// Sets how long the application has to reply to each call,
+    /// for applications which are known to be slow.
+    ///
+    /// By default, [`ClientBuilder::call_timeout`] is used.
+    #[must_use]
+    pub fn call_timeout(mut self, call_timeout: Duration) -> Self {
+        self.call_timeout = Some(call_timeout);
+        self
+    }
+}
 
-        Ok(Self {
-            connection,
-            tx,
-            _rx: rx,
-            items,
-        })
+type EventFn = dyn FnMut(&Event) + Send;
+
+/// A callback invoked with each event as it is produced.
+#[derive(Clone)]
+struct EventCallback(Arc<Mutex<EventFn>>);
+
+impl Debug for EventCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventCallback")
     }
+}
 
-    /// Processes an incoming item to send the initial add event,
-    /// then set up listeners for it and its menu.
-    async fn handle_item(
-        address: &str,
-        connection: Connection,
-        tx: broadcast::Sender<Event>,
-        items: Arc<Mutex<State>>,
-    ) -> crate::error::Result<()> {
-        let (destination, path) = parse_address(address);
+/// State shared between the client and its background tasks.
+#[derive(Debug, Clone)]
+struct Context {
+    connection: Connection,
+    tx: broadcast::Sender<Event>,
+    items: Arc<Mutex<State>>,
+    config: Arc<Config>,
+    tasks: Arc<Mutex<Tasks>>,
+    /// The object path of each item, keyed on its address.
+    paths: Arc<Mutex<HashMap<String, String>>>,
+    /// The latest `AboutToShow` call for each menu item,
+    /// keyed on the item address and menu item id.
+    about_to_show: Arc<Mutex<HashMap<(String, i32), AboutToShowCall>>>,
+    /// Proxies for calls made through the client, keyed on the item address,
+    /// so that each call does not build a new one.
+    proxies: Arc<Mutex<HashMap<String, Proxies>>>,
+    /// The addresses of items still being loaded.
+    pending: Arc<Mutex<HashSet<String>>>,
+    /// The activity of each item, keyed on its address.
+    stats: Arc<Mutex<HashMap<String, ItemStats>>>,
+    /// How the properties of each item are read, keyed on its address.
+    /// Items read as normal are not included.
+    property_access: Arc<Mutex<HashMap<String, PropertyAccess>>>,
+    /// The unique name now owning the well-known name each item registered with,
+    /// keyed on its address.
+    /// Only items whose name moved to another connection are included.
+    owners: Arc<Mutex<HashMap<String, String>>>,
+    /// What each item implements, keyed on its address.
+    /// Only items which were introspected are included.
+    capabilities: Arc<Mutex<HashMap<String, Capabilities>>>,
+    /// The pixmap properties already fetched by [`Client::load_icon`] for each item,
+    /// keyed on its address.
+    loaded_pixmaps: Arc<Mutex<HashMap<String, HashSet<&'static str>>>>,
+    /// Subscribers from [`Client::subscribe_bounded`].
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Event>>>>,
+}
 
-        let properties_proxy = PropertiesProxy::builder(&connection)
-            .destination(destination.to_string())?
-            .path(path.clone())?
-            .build()
-            .await?;
+/// The proxies built for calls to an item and its menu.
+#[derive(Debug, Default)]
+struct Proxies {
+    item: Option<StatusNotifierItemProxy<'static>>,
+    menu: Option<DBusMenuProxy<'static>>,
+}
 
-        let properties = Self::get_item_properties(destination, &path, &properties_proxy).await?;
+/// The signals watched for an item.
+struct ItemSignals {
+    props_changed: SignalStream<'static>,
+    standard_props_changed: PropertiesChangedStream,
+    disconnect: NameOwnerChangedStream,
+    /// Owner changes of the well-known name the item registered with, if any.
+    transfer: Option<NameOwnerChangedStream>,
+}
 
-        items
-            .lock()
-            .expect("mutex lock should succeed")
-            .insert(destination.into(), (properties.clone(), None));
+/// How the properties of an item are read, found when it is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PropertyAccess {
+    /// Through the standard properties interface.
+    Properties,
+    /// Through `Get` for each property,
+    /// for items which answer `GetAll` with an error or without their properties.
+    Get,
+    /// Not at all, for items which do not implement the properties interface.
+    /// Only the values carried by change signals are known.
+    None,
+}
 
-        tx.send(Event::Add(
-            destination.to_string(),
-            properties.clone().into(),
-        ))?;
+/// A call to `AboutToShow`, shared by callers while it is in flight
+/// and for the cooldown after.
+#[derive(Debug)]
+struct AboutToShowCall {
+    result: Arc<OnceCell<bool>>,
+    started: Instant,
+}
 
-        {
-            let connection = connection.clone();
-            let destination = destination.to_string();
-            let items = items.clone();
-            let tx = tx.clone();
+/// Marks the item at an address as being loaded, until dropped.
+struct PendingItem {
+    pending: Arc<Mutex<HashSet<String>>>,
+    address: String,
+    clock: Arc<dyn Clock>,
+    started: Instant,
+}
 
-            spawn(async move {
-                Self::watch_item_properties(
-                    &destination,
-                    &path,
-                    &connection,
-                    properties_proxy,
-                    items,
-                    tx,
-                )
-                .await?;
+impl PendingItem {
+    /// Marks the item at `address` as being loaded,
+    /// or returns `None` if it already is.
+    fn start(ctx: &Context, address: &str) -> Option<Self> {
+        lock(&ctx.pending)
+            .insert(address.to_string())
+            .then(|| Self {
+                pending: ctx.pending.clone(),
+                address: address.to_string(),
+                clock: ctx.config.clock.clone(),
+                started: ctx.config.clock.now(),
+            })
+    }
+}
 
-                debug!("Stopped watching {destination}{path}");
-                Ok::<(), Error>(())
-            });
+impl Drop for PendingItem {
+    fn drop(&mut self) {
+        lock(&self.pending).remove(&self.address);
+
+        let elapsed = self.clock.elapsed(self.started);
+        if cfg!(debug_assertions) && elapsed > SLOW_LOAD_THRESHOLD {
+            warn!("{} took {elapsed:.1?} to load", self.address);
         }
+    }
+}
+
+/// Handles for aborting the background tasks of a client.
+#[derive(Debug, Default)]
+struct Tasks {
+    /// Tasks not tied to a single item.
+    client: Vec<AbortHandle>,
+    /// Tasks watching each item, keyed on the item address.
+    items: HashMap<String, Vec<AbortHandle>>,
+    /// The task watching the menu of each item, keyed on the item address.
+    menus: HashMap<String, AbortHandle>,
+}
 
-        if let Some(menu) = properties.menu {
-            let destination = destination.to_string();
+impl Context {
+    /// Spawns a background task, logging any error it returns.
+    ///
+    /// The task is aborted when the client is restarted or dropped.
+    fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let handle = spawn_logged(task);
 
-            tx.send(Event::Update(
-                destination.clone(),
-                UpdateEvent::MenuConnect(menu.clone()),
-            ))?;
+        let mut tasks = lock(&self.tasks);
+        tasks.client.retain(|task| !task.is_finished());
+        tasks.client.push(handle);
+    }
 
-            spawn(async move {
-                Self::watch_menu(destination, &menu, &connection, tx, items).await?;
-                Ok::<(), Error>(())
-            });
+    /// Spawns a background task for the item at `address`.
+    ///
+    /// The task is aborted when the item is removed, or the client is restarted or dropped.
+    /// If it fails or panics, the item is removed, see [`Context::spawn_isolated`].
+    fn spawn_for_item<F>(&self, address: &str, task: F)
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        if cfg!(debug_assertions) {
+            self.check_leaked_tasks();
         }
 
-        Ok(())
+        let handle = self.spawn_isolated(address, true, task);
+
+        let mut tasks = lock(&self.tasks);
+        tasks.items.retain(|_, tasks| {
+            tasks.retain(|task| !task.is_finished());
+            !tasks.is_empty()
+        });
+        tasks
+            .items
+            .entry(address.to_string())
+            .or_default()
+            .push(handle);
     }
 
-    /// Gets the properties for an SNI item.
-    async fn get_item_properties(
-        destination: &str,
-        path: &str,
-        properties_proxy: &PropertiesProxy<'_>,
-    ) -> crate::error::Result<StatusNotifierItem> {
-        let properties = properties_proxy
-            .get_all(
-                InterfaceName::from_static_str(PROPERTIES_INTERFACE)
-                    .expect("to be valid interface name"),
-            )
-            .await;
+    /// Spawns the task watching the menu of the item at `address`,
+    /// aborting the previous one if there was one.
+    ///
+    /// If the task fails or panics, the item is kept, see [`Context::spawn_isolated`].
+    fn spawn_for_menu<F>(&self, address: &str, task: F)
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        if cfg!(debug_assertions) {
+            self.check_leaked_tasks();
+        }
 
-        let properties = match properties {
-            Ok(properties) => properties,
-            Err(err) => {
-                error!("Error fetching properties from {destination}{path}: {err:?}");
-                return Err(err.into());
-            }
-        };
+        let handle = self.spawn_isolated(address, false, task);
 
-        StatusNotifierItem::try_from(DBusProps(properties))
+        let mut tasks = lock(&self.tasks);
+        tasks.menus.retain(|_, task| !task.is_finished());
+        if let Some(previous) = tasks.menus.insert(address.to_string(), handle) {
+            previous.abort();
+        }
     }
 
-    /// Watches an SNI item's properties,
-    /// sending an update event whenever they change.
-    async fn watch_item_properties(
-        destination: &str,
-        path: &str,
-        connection: &Connection,
-        properties_proxy: PropertiesProxy<'_>,
-        items: Arc<Mutex<State>>,
-        tx: broadcast::Sender<Event>,
-    ) -> crate::error::Result<()> {
-        let notifier_item_proxy = StatusNotifierItemProxy::builder(connection)
-            .destination(destination)?
-            .path(path)?
-            .build()
-            .await?;
+    /// Spawns `task` for the item at `address`, containing any failure to that item.
+    ///
+    /// If the task returns an error or panics, [`Warning::TaskFailed`] is sent.
+    /// If `remove` is set, the item is then removed too,
+    /// rather than left in the tray with nothing watching it.
+    /// It is not unregistered from the watcher,
+    /// so it is loaded again if it registers again, or the client is restarted.
+    fn spawn_isolated<F>(&self, address: &str, remove: bool, task: F) -> AbortHandle
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let ctx = self.clone();
+        let address = address.to_string();
 
-        let dbus_proxy = DBusProxy::new(connection).await?;
+        spawn(async move {
+            let message = match AssertUnwindSafe(task).catch_unwind().await {
+                Ok(Ok(())) => return,
+                Ok(Err(err)) => err.to_string(),
+                Err(panic) => format!("panicked: {}", panic_message(panic.as_ref())),
+            };
+
+            error!("[{address}] background task failed: {message}");
+
+            let warning = Event::Warning(address.clone(), Warning::TaskFailed { message });
+            let mut result = ctx.send(warning).await;
+            if remove && result.is_ok() && lock(&ctx.items).contains_key(&address) {
+                result = ctx.send(Event::Remove(address.clone())).await;
+            }
+
+            if let Err(err) = result {
+                error!("[{address}] failed to report task failure: {err}");
+            }
+        })
+        .abort_handle()
+    }
+
+    /// Warns about any item or menu task still running after its item was removed,
+    /// which means it was never aborted.
+    ///
+    /// This is only checked in debug builds.
+    fn check_leaked_tasks(&self) {
+        let running = {
+            let tasks = lock(&self.tasks);
+
+            let item_tasks = tasks
+                .items
+                .iter()
+                .filter(|(_, tasks)| tasks.iter().any(|task| !task.is_finished()));
+            let menu_tasks = tasks.menus.iter().filter(|(_, task)| !task.is_finished());
+
+            item_tasks
+                .map(|(address, _)| address)
+                .chain(menu_tasks.map(|(address, _)| address))
+                .cloned()
+                .collect::<HashSet<_>>()
+        };
+
+        let items = lock(&self.items);
+        for address in running
+            .iter()
+            .filter(|address| !items.contains_key(*address))
+        {
+            warn!("background task for removed item {address} is still running");
+        }
+    }
+
+    /// Calls `AboutToShow` for the menu item `id` of the item at `address`.
+    ///
+    /// Concurrent callers share a single call,
+    /// and its result is reused until the configured cooldown has passed since it started.
+    async fn about_to_show(
+        &self,
+        proxy: &DBusMenuProxy<'_>,
+        address: &str,
+        id: i32,
+    ) -> Result<bool> {
+        let result = {
+            let mut calls = lock(&self.about_to_show);
+            let key = (address.to_string(), id);
+
+            match calls.get(&key) {
+                Some(call)
+                    if !call.result.initialized()
+                        || self.config.clock.elapsed(call.started)
+                            < self.config.about_to_show_cooldown =>
+                {
+                    call.result.clone()
+                }
+                _ => {
+                    let result = Arc::<OnceCell<bool>>::default();
+                    calls.insert(
+                        key,
+                        AboutToShowCall {
+                            result: result.clone(),
+                            started: self.config.clock.now(),
+                        },
+                    );
+                    result
+                }
+            }
+        };
+
+        let needs_update = result
+            .get_or_try_init(|| self.call(address, "AboutToShow", proxy.about_to_show(id)))
+            .await?;
+
+        Ok(*needs_update)
+    }
+
+    /// Prepares the submenu of the menu item `id` for display,
+    /// merging its freshly fetched children into the cached menu and sending it on.
+    async fn load_submenu(
+        &self,
+        proxy: &DBusMenuProxy<'_>,
+        address: &str,
+        id: i32,
+    ) -> Result<TrayMenu> {
+        self.about_to_show(proxy, address, id).await?;
+
+        let menu_properties = self.config.menu_properties();
+        let get_layout = proxy.get_layout(id, self.config.menu_layout_depth, &menu_properties);
+        let layout = self.call(address, "GetLayout", get_layout).await?;
+        let mut submenu = TrayMenu::from_layout(layout, self.config.max_menu_depth)?;
+        submenu.sanitize(self.config.max_text_length);
+        if self.config.visible_menu_items_only {
+            submenu.retain_visible();
+        }
+
+        let cached = lock(&self.items)
+            .get(address)
+            .and_then(|(_, menu)| menu.clone());
+        let mut menu = match cached {
+            Some(menu) => menu,
+            None => fetch_menu(proxy, address, self).await?,
+        };
+
+        menu.find_item_mut(id)
+            .ok_or(Error::InvalidData("menu has no item with the given id"))?
+            .submenu = submenu.submenus;
+
+        self.send(Event::Update(
+            address.to_string(),
+            UpdateEvent::Menu(menu.clone()),
+        ))
+        .await?;
+
+        Ok(menu)
+    }
+
+    /// Fetches the properties of the menu items `ids` in one call,
+    /// replacing them in the cached menu and sending it on.
+    ///
+    /// The submenus of the items are kept.
+    /// Ids missing from the cached menu are skipped,
+    /// and nothing is done if no menu is cached.
+    async fn refresh_menu_items(
+        &self,
+        proxy: &DBusMenuProxy<'_>,
+        address: &str,
+        ids: &[i32],
+    ) -> Result<Option<TrayMenu>> {
+        let menu_properties = self.config.menu_properties();
+        let get_group_properties = proxy.get_group_properties(ids, &menu_properties);
+        let (_, properties) = self
+            .call(address, "GetGroupProperties", get_group_properties)
+            .await?;
+
+        let items = properties
+            .into_iter()
+            .map(|(id, properties)| MenuItem::from_properties(id, properties))
+            .collect::<Result<Vec<_>>>()?;
+
+        let Some(mut menu) = lock(&self.items)
+            .get(address)
+            .and_then(|(_, menu)| menu.clone())
+        else {
+            return Ok(None);
+        };
+
+        for mut item in items {
+            item.sanitize(self.config.max_text_length);
+            if let Some(cached) = menu.find_item_mut(item.id) {
+                item.submenu = std::mem::take(&mut cached.submenu);
+                *cached = item;
+            }
+        }
+
+        self.send(Event::Update(
+            address.to_string(),
+            UpdateEvent::Menu(menu.clone()),
+        ))
+        .await?;
+
+        Ok(Some(menu))
+    }
+
+    /// Loads the submenus of `items` in the background, if enabled,
+    /// so they are ready before they are hovered.
+    fn prefetch_submenus(&self, proxy: DBusMenuProxy<'static>, address: &str, items: &[MenuItem]) {
+        if !self.config.prefetch_submenus {
+            return;
+        }
+
+        let ids = items
+            .iter()
+            .filter(|item| item.has_submenu())
+            .map(|item| item.id)
+            .collect::<Vec<_>>();
+
+        if ids.is_empty() {
+            return;
+        }
+
+        let ctx = self.clone();
+        let task_address = address.to_string();
+        self.spawn_for_item(address, async move {
+            for id in ids {
+                if let Err(err) = ctx.load_submenu(&proxy, &task_address, id).await {
+                    debug!("[{task_address}] failed to prefetch submenu {id}: {err}");
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    /// Applies `event` to the item state,
+    /// then passes it to the callback if set and sends it to subscribers.
+    ///
+    /// Removing an item aborts its background tasks,
+    /// and updates for items which are no longer present are dropped,
+    /// so that a fetch finishing late cannot bring a removed item back.
+    ///
+    /// This waits for any full subscribers from [`Client::subscribe_bounded`].
+    ///
+    /// Text from items is sanitized first, see [`ClientBuilder::max_text_length`].
+    async fn send(&self, mut event: Event) -> Result<()> {
+        sanitize_event(&mut event, self.config.max_text_length);
+
+        {
+            let mut items = lock(&self.items);
+
+            if let Event::Update(address, _) = &event {
+                if !items.contains_key(address) {
+                    trace!("dropping update for removed item '{address}'");
+                    return Ok(());
+                }
+            }
+
+            // the watcher and the item's own task can both see it go
+            if let Event::Remove(address) = &event {
+                if !items.contains_key(address) {
+                    trace!("dropping remove for removed item '{address}'");
+                    return Ok(());
+                }
+            }
+
+            state::apply(&mut items, &event);
+        }
+
+        if let Event::Remove(address) = &event {
+            lock(&self.paths).remove(address);
+            lock(&self.about_to_show).retain(|(call_address, _), _| call_address != address);
+            lock(&self.proxies).remove(address);
+            lock(&self.stats).remove(address);
+            lock(&self.property_access).remove(address);
+            lock(&self.owners).remove(address);
+            lock(&self.capabilities).remove(address);
+            lock(&self.loaded_pixmaps).remove(address);
+        }
+
+        if let Some(EventCallback(on_event)) = &self.config.on_event {
+            (lock(on_event))(&event);
+        }
+
+        let subscribers = lock(&self.subscribers).clone();
+        for subscriber in subscribers {
+            if subscriber.send(event.clone()).await.is_err() {
+                lock(&self.subscribers).retain(|other| !other.same_channel(&subscriber));
+            }
+        }
+
+        // this is often called from a task of the removed item,
+        // so the tasks are only aborted once the event has been delivered
+        if let Event::Remove(address) = &event {
+            self.abort_item_tasks(address);
+        }
+
+        self.tx.send(event)?;
+        Ok(())
+    }
+
+    /// Gets how long the item at `address` has to reply to a call.
+    ///
+    /// Items which have not been added yet use the default timeout.
+    fn call_timeout(&self, address: &str) -> Duration {
+        let items = lock(&self.items);
+        match items.get(address) {
+            Some((item, _)) => self.config.call_timeout(&item.id),
+            None => self.config.call_timeout,
+        }
+    }
+
+    /// Awaits `call` to `method` on the item at `address`,
+    /// failing with a timeout error once the item's call timeout has passed.
+    ///
+    /// If the call is still in flight after the slow call threshold,
+    /// [`Warning::SlowCall`] is sent and the call carries on.
+    async fn call<T, E>(
+        &self,
+        address: &str,
+        method: &str,
+        call: impl Future<Output = std::result::Result<T, E>>,
+    ) -> Result<T>
+    where
+        Error: From<E>,
+    {
+        let limit = self.call_timeout(address);
+        let call = with_timeout(&*self.config.clock, limit, method, call);
+
+        let Some(threshold) = self.config.slow_call_threshold.filter(|&t| t < limit) else {
+            return call.await;
+        };
+
+        let clock = &*self.config.clock;
+        let started = clock.now();
+        let mut call = std::pin::pin!(call);
+        if let Some(result) = clock::timeout(clock, threshold, call.as_mut()).await {
+            return result;
+        }
+
+        warn!("[{address}] {method} has been in flight for {threshold:?}");
+        let warning = Warning::SlowCall {
+            method: method.to_string(),
+            elapsed: clock.elapsed(started),
+        };
+        self.send(Event::Warning(address.to_string(), warning))
+            .await?;
+
+        let result = call.await;
+        debug!(
+            "[{address}] {method} finished after {:?}",
+            clock.elapsed(started)
+        );
+        result
+    }
+
+    /// Gets the menu proxy for the item at `address`,
+    /// and whether it was created rather than taken from the cache.
+    async fn menu_proxy(
+        &self,
+        address: &str,
+        menu_path: &str,
+    ) -> Result<(DBusMenuProxy<'static>, bool)> {
+        // the menu can move, so a proxy for another path is replaced
+        if let Some(proxy) = lock(&self.proxies)
+            .get(address)
+            .and_then(|proxies| proxies.menu.clone())
+            .filter(|proxy| proxy.inner().path().as_str() == menu_path)
+        {
+            return Ok((proxy, false));
+        }
+
+        let proxy = DBusMenuProxy::builder(&self.connection)
+            .destination(self.owner(address))?
+            .path(menu_path.to_string())?
+            .build()
+            .await?;
+
+        lock(&self.proxies)
+            .entry(address.to_string())
+            .or_default()
+            .menu = Some(proxy.clone());
+        Ok((proxy, true))
+    }
+
+    /// Gets the bus name calls to the item at `address` are sent to.
+    fn owner(&self, address: &str) -> String {
+        lock(&self.owners)
+            .get(address)
+            .cloned()
+            .unwrap_or_else(|| address.to_string())
+    }
+
+    /// Gets the object path the item at `address` registered at.
+    fn item_path(&self, address: &str) -> String {
+        lock(&self.paths)
+            .get(address)
+            .cloned()
+            .unwrap_or_else(|| ITEM_OBJECT.to_string())
+    }
+
+    /// Gets the address of the item owned by the bus name `owner`.
+    fn address(&self, owner: &str) -> String {
+        lock(&self.owners)
+            .iter()
+            .find(|(_, item_owner)| *item_owner == owner)
+            .map_or_else(|| owner.to_string(), |(address, _)| address.clone())
+    }
+
+    /// Gets whether the item at `address` is known not to implement `method`,
+    /// as found by introspecting it.
+    fn lacks_method(&self, address: &str, method: &str) -> bool {
+        lock(&self.capabilities)
+            .get(address)
+            .is_some_and(|capabilities| !capabilities.has_method(method))
+    }
+
+    /// Gets whether the item at `address` is known to have no signals to announce changes,
+    /// as found by introspecting it.
+    fn lacks_signals(&self, address: &str) -> bool {
+        lock(&self.capabilities)
+            .get(address)
+            .is_some_and(|capabilities| capabilities.signals.is_empty())
+    }
+
+    /// Gets whether the menu of the item at `address` is known not to implement `method`,
+    /// as found by introspecting it.
+    fn lacks_menu_method(&self, address: &str, method: &str) -> bool {
+        lock(&self.capabilities)
+            .get(address)
+            .is_some_and(|capabilities| !capabilities.has_menu_method(method))
+    }
+
+    /// Gets how the properties of the item at `address` are read.
+    fn property_access(&self, address: &str) -> PropertyAccess {
+        lock(&self.property_access)
+            .get(address)
+            .copied()
+            .unwrap_or(PropertyAccess::Properties)
+    }
+
+    /// Records that `signals` signals from the item at `address`
+    /// were handled, starting at `started`.
+    fn record_signals(&self, address: &str, signals: usize, started: Instant) {
+        let mut stats = lock(&self.stats);
+        let stats = stats.entry(address.to_string()).or_default();
+
+        stats.signals += signals as u64;
+        stats.last_handled = Some(started.elapsed());
+    }
+
+    /// Aborts the background tasks for the item at `address`.
+    fn abort_item_tasks(&self, address: &str) {
+        let mut tasks = lock(&self.tasks);
+        let item_tasks = tasks.items.remove(address).into_iter().flatten();
+        let menu_task = tasks.menus.remove(address);
+
+        for task in item_tasks.chain(menu_task) {
+            task.abort();
+        }
+    }
+
+    /// Aborts the task watching the menu of the item at `address`, if there is one.
+    fn abort_menu_task(&self, address: &str) {
+        if let Some(task) = lock(&self.tasks).menus.remove(address) {
+            task.abort();
+        }
+    }
+
+    /// Aborts all background tasks spawned for this context.
+    fn abort_tasks(&self) {
+        let tasks = std::mem::take(&mut *lock(&self.tasks));
+
+        for task in tasks
+            .client
+            .into_iter()
+            .chain(tasks.items.into_values().flatten())
+            .chain(tasks.menus.into_values())
+        {
+            task.abort();
+        }
+    }
+}
+
+/// Spawns `task` onto the runtime, logging any error it returns.
+fn spawn_logged<F>(task: F) -> AbortHandle
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    spawn(async move {
+        if let Err(err) = task.await {
+            error!("{err}");
+        }
+    })
+    .abort_handle()
+}
+
+/// Sanitizes the text carried by `event`, see [`ClientBuilder::max_text_length`].
+fn sanitize_event(event: &mut Event, max_length: usize) {
+    match event {
+        Event::Add(_, item) => item.sanitize(max_length),
+        Event::Update(_, UpdateEvent::Title(Some(title))) => sanitize_text(title, max_length),
+        Event::Update(_, UpdateEvent::Tooltip(Some(tooltip))) => tooltip.sanitize(max_length),
+        Event::Update(_, UpdateEvent::Menu(menu)) => menu.sanitize(max_length),
+        Event::Update(_, UpdateEvent::MenuDiff(diffs)) => {
+            for diff in diffs {
+                if let Some(Some(label)) = &mut diff.update.label {
+                    sanitize_text(label, max_length);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Gets the message a task panicked with, if it was a string.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown cause"
+    }
+}
+
+/// Builder for configuring and creating a [`Client`].
+///
+/// This can be obtained from [`Client::builder`].
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    config: Config,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            config: Config {
+                track_menus: true,
+                prefetch_menus: false,
+                expected_items: 0,
+                liveness_interval: None,
+                idle_ping_interval: None,
+                poll_interval: None,
+                debug_destination: None,
+                on_event: None,
+                item_configs: HashMap::new(),
+                lazy_pixmaps: false,
+                visible_menu_items_only: false,
+                max_menu_depth: menu::MAX_MENU_DEPTH,
+                menu_layout_depth: 10,
+                menu_icon_data: true,
+                about_to_show_cooldown: Duration::from_millis(500),
+                prefetch_submenus: false,
+                channel_capacity: 32,
+                lag_policy: LagPolicy::Error,
+                retry_policy: RetryPolicy::default(),
+                call_timeout: Duration::from_secs(5),
+                slow_call_threshold: None,
+                max_text_length: 1024,
+                introspect_items: false,
+                bus_address: None,
+                capture: None,
+                clock: Arc::new(SystemClock),
+            },
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Sets whether to listen for layout and property changes on item menus.
+    ///
+    /// Menus are listened to from the first time they are used,
+    /// unless they are prefetched, see [`ClientBuilder::prefetch_menus`].
+    ///
+    /// When disabled, menus are only fetched when calling [`Client::open_menu`],
+    /// and no menu events are sent outside of that.
+    ///
+    /// Default: `true`
+    #[must_use]
+    pub fn track_menus(mut self, track_menus: bool) -> Self {
+        self.config.track_menus = track_menus;
+        self
+    }
+
+    /// Sets whether to fetch the layout of each menu as soon as its item is registered.
+    ///
+    /// By default, a menu is only connected to the first time it is needed,
+    /// such as when calling [`Client::open_menu`].
+    /// This avoids creating a proxy and subscribing to the menu signals for each item at startup,
+    /// for menus which are never opened.
+    ///
+    /// This has no effect if menus are not tracked.
+    ///
+    /// Default: `false`
+    #[must_use]
+    pub fn prefetch_menus(mut self, prefetch_menus: bool) -> Self {
+        self.config.prefetch_menus = prefetch_menus;
+        self
+    }
+
+    /// Sets the number of items expected to be registered,
+    /// so that space for them can be reserved up front.
+    ///
+    /// This is only a hint; any number of items can still be tracked.
+    ///
+    /// Default: `0`
+    #[must_use]
+    pub fn expected_items(mut self, expected_items: usize) -> Self {
+        self.config.expected_items = expected_items;
+        self
+    }
+
+    /// Sets how often to check each item is still responsive,
+    /// for detecting items which stop responding without leaving the bus.
+    ///
+    /// Every interval, the `Status` property of each item is requested.
+    /// If the item does not reply, it is removed as if it had disconnected.
+    /// Checks are spread out across the interval rather than sent for all items at once.
+    ///
+    /// Default: disabled
+    #[must_use]
+    pub fn liveness_interval(mut self, liveness_interval: Duration) -> Self {
+        self.config.liveness_interval = Some(liveness_interval);
+        self
+    }
+
+    /// Sets how long an item can go without sending any signal before it is pinged,
+    /// for detecting items which left the bus without the client noticing.
+    ///
+    /// Quiet items are sent `org.freedesktop.DBus.Peer.Ping` every interval.
+    /// If the bus reports that nothing owns the item's name any more,
+    /// it is removed as if it had disconnected.
+    /// Unlike [`ClientBuilder::liveness_interval`],
+    /// items which are slow to reply are kept.
+    ///
+    /// Default: disabled
+    #[must_use]
+    pub fn idle_ping_interval(mut self, idle_ping_interval: Duration) -> Self {
+        self.config.idle_ping_interval = Some(idle_ping_interval);
+        self
+    }
+
+    /// Sets how often to read the properties of items which do not announce changes,
+    /// so that their icon and status are still updated eventually.
+    ///
+    /// Items are polled if they have sent no signal since they were added,
+    /// or if introspecting them showed they have no signals,
+    /// see [`ClientBuilder::introspect_items`].
+    /// Each poll sends updates for any properties which changed.
+    ///
+    /// Default: disabled
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.config.poll_interval = Some(poll_interval);
+        self
+    }
+
+    /// Sets a single item to log raw messages for, to help debug a misbehaving application.
+    ///
+    /// The payload of every signal from the item and its menu,
+    /// and of every property fetched from it,
+    /// is logged at `debug` level with the `system_tray::raw` target.
+    /// Either the unique or well-known name of the item can be given.
+    ///
+    /// Default: none
+    #[must_use]
+    pub fn debug_destination(mut self, destination: impl Into<String>) -> Self {
+        self.config.debug_destination = Some(destination.into());
+        self
+    }
+
+    /// Sets a callback to invoke with each event as it is produced,
+    /// before it is sent to subscribers.
+    ///
+    /// This avoids the latency and copy of the broadcast channel,
+    /// for consumers which must react immediately.
+    ///
+    /// The callback runs inline on the client's background tasks,
+    /// so it must be fast, must not block, and must not panic.
+    /// Calls are never made concurrently.
+    #[must_use]
+    pub fn on_event(mut self, on_event: impl FnMut(&Event) + Send + 'static) -> Self {
+        self.config.on_event = Some(EventCallback(Arc::new(Mutex::new(on_event))));
+        self
+    }
+
+    /// Sets whether to drop invisible items from menu layouts before they are sent on,
+    /// along with any separators left doubled up as a result.
+    ///
+    /// See [`TrayMenu::retain_visible`].
+    ///
+    /// Default: `false`
+    #[must_use]
+    pub fn visible_menu_items_only(mut self, visible_menu_items_only: bool) -> Self {
+        self.config.visible_menu_items_only = visible_menu_items_only;
+        self
+    }
+
+    /// Sets the number of levels of submenus requested when fetching a menu layout,
+    /// or `-1` for all of them.
+    ///
+    /// A low depth keeps large menus quick to fetch.
+    /// Deeper levels can then be loaded as they are opened,
+    /// using [`Client::open_submenu`].
+    ///
+    /// Default: `10`
+    #[must_use]
+    pub fn menu_layout_depth(mut self, menu_layout_depth: i32) -> Self {
+        self.config.menu_layout_depth = menu_layout_depth;
+        self
+    }
+
+    /// Sets whether to load the submenus of a menu in the background as soon as it is opened.
+    ///
+    /// When [`Client::open_menu`] or [`Client::open_submenu`] is called,
+    /// each item with a submenu is then prepared with `AboutToShow`
+    /// and has its children fetched,
+    /// so applications which build their submenus lazily show them without delay.
+    ///
+    /// Default: `false`
+    #[must_use]
+    pub fn prefetch_submenus(mut self, prefetch_submenus: bool) -> Self {
+        self.config.prefetch_submenus = prefetch_submenus;
+        self
+    }
+
+    /// Sets how long the result of `AboutToShow` is reused for the same menu item.
+    ///
+    /// Some applications take hundreds of milliseconds to answer,
+    /// so UIs calling it for each hover could otherwise stall them.
+    /// Concurrent calls for the same menu item are always shared.
+    ///
+    /// Default: 500ms
+    #[must_use]
+    pub fn about_to_show_cooldown(mut self, about_to_show_cooldown: Duration) -> Self {
+        self.config.about_to_show_cooldown = about_to_show_cooldown;
+        self
+    }
+
+    /// Sets the number of events buffered for each subscriber.
+    ///
+    /// A subscriber which falls further behind than this misses the oldest events,
+    /// so consumers with bursty load or slow handling should raise it.
+    /// The capacity is at least 1.
+    ///
+    /// Default: `32`
+    #[must_use]
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.config.channel_capacity = channel_capacity.max(1);
+        self
+    }
+
+    /// Sets what receivers from [`Client::events`] do when they miss events.
+    ///
+    /// Receivers from [`Client::subscribe`] always return [`RecvError::Lagged`].
+    ///
+    /// Default: [`LagPolicy::Error`]
+    #[must_use]
+    pub fn lag_policy(mut self, lag_policy: LagPolicy) -> Self {
+        self.config.lag_policy = lag_policy;
+        self
+    }
+
+    /// Sets how failed calls fetching item properties and menu layouts are retried.
+    ///
+    /// Only errors which are likely to pass are retried, such as timeouts.
+    /// Once out of attempts, [`Warning::CallFailed`] is sent.
+    ///
+    /// Default: [`RetryPolicy::default`]
+    #[must_use]
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.config.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets how long items have to reply to each call
+    /// fetching their properties, menu layouts or preparing a menu for display.
+    ///
+    /// Calls which take any longer fail with a timeout error,
+    /// so an application which has hung does not hold up its item forever.
+    /// This can be overridden for an application using [`ItemConfig::call_timeout`].
+    ///
+    /// Default: 5 seconds
+    #[must_use]
+    pub fn call_timeout(mut self, call_timeout: Duration) -> Self {
+        self.config.call_timeout = call_timeout;
+        self
+    }
+
+    /// Sets how long a call to an item can take before [`Warning::SlowCall`] is sent,
+    /// to help find the application making the tray sluggish.
+    ///
+    /// The call carries on until it completes or reaches its timeout,
+    /// see [`ClientBuilder::call_timeout`].
+    ///
+    /// Default: disabled
+    #[must_use]
+    pub fn slow_call_threshold(mut self, slow_call_threshold: Duration) -> Self {
+        self.config.slow_call_threshold = Some(slow_call_threshold);
+        self
+    }
+
+    /// Sets whether to fetch the PNG icons of menu items.
+    ///
+    /// Icon data is often the bulk of a layout,
+    /// so consumers which only show icon names, or no icons, can skip it.
+    ///
+    /// Default: `true`
+    #[must_use]
+    pub fn menu_icon_data(mut self, menu_icon_data: bool) -> Self {
+        self.config.menu_icon_data = menu_icon_data;
+        self
+    }
+
+    /// Sets the most characters kept of item titles, tooltips and menu labels.
+    ///
+    /// Control characters, such as terminal escape sequences, are always removed from them,
+    /// and anything longer than this is cut short,
+    /// protecting consumers which show or log them from broken or malicious applications.
+    ///
+    /// Default: `1024`
+    #[must_use]
+    pub fn max_text_length(mut self, max_text_length: usize) -> Self {
+        self.config.max_text_length = max_text_length;
+        self
+    }
+
+    /// Sets the number of levels of submenus accepted in a menu layout.
+    ///
+    /// Layouts nested any deeper, or with the same item id more than once,
+    /// are rejected with an error rather than parsed,
+    /// protecting against broken or malicious applications.
+    ///
+    /// Default: `32`
+    #[must_use]
+    pub fn max_menu_depth(mut self, max_menu_depth: usize) -> Self {
+        self.config.max_menu_depth = max_menu_depth;
+        self
+    }
+
+    /// Sets whether to skip fetching icon pixmaps until they are needed.
+    ///
+    /// When enabled, the pixmap properties of items are not fetched when they are added,
+    /// and are instead loaded by [`Client::load_icon`].
+    /// This reduces the bus traffic at startup
+    /// for consumers which only show some items, or use icon names.
+    ///
+    /// Default: `false`
+    #[must_use]
+    pub fn lazy_pixmaps(mut self, lazy_pixmaps: bool) -> Self {
+        self.config.lazy_pixmaps = lazy_pixmaps;
+        self
+    }
+
+    /// Sets whether to introspect each item and its menu when it is registered,
+    /// finding which interfaces and optional methods they implement.
+    ///
+    /// The result is available from [`Client::capabilities`],
+    /// and lets the client skip calls the item is known not to implement,
+    /// rather than making them and handling the error.
+    /// Menus whose object does not implement the menu interface are not connected.
+    ///
+    /// This costs two calls per item at startup.
+    ///
+    /// Default: `false`
+    #[must_use]
+    pub fn introspect_items(mut self, introspect_items: bool) -> Self {
+        self.config.introspect_items = introspect_items;
+        self
+    }
+
+    /// Sets the address of the bus to connect to, instead of the session bus,
+    /// such as `unix:path=/run/user/1000/bus`.
+    ///
+    /// The client also connects to this address when reconnecting.
+    ///
+    /// Default: the session bus
+    #[must_use]
+    pub fn bus_address(mut self, bus_address: impl Into<String>) -> Self {
+        self.config.bus_address = Some(bus_address.into());
+        self
+    }
+
+    /// Records every message the client receives to a fixture file at `path`,
+    /// appending to the file if it exists.
+    ///
+    /// The recording can be replayed without a bus using [`Fixture`](crate::fixture::Fixture),
+    /// to reproduce problems seen with a particular application.
+    ///
+    /// Default: not recorded
+    #[must_use]
+    pub fn capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.capture = Some(path.into());
+        self
+    }
+
+    /// Sets the clock used for the client's delays, timeouts and periodic checks,
+    /// such as a manually advanced clock in tests.
+    ///
+    /// See [`Clock`].
+    ///
+    /// Default: [`SystemClock`]
+    #[must_use]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.config.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets options for the application whose items have the given `id`,
+    /// replacing any previously set for it.
+    ///
+    /// See [`ItemConfig`].
+    #[must_use]
+    pub fn item_config(mut self, id: impl Into<String>, config: ItemConfig) -> Self {
+        self.config.item_configs.insert(id.into(), config);
+        self
+    }
+
+    /// Creates and initializes the client.
+    ///
+    /// See [`Client::new`].
+    ///
+    /// # Errors
+    ///
+    /// If the initialization fails for any reason,
+    /// for example if unable to connect to the bus,
+    /// this method will return an error.
+    pub async fn build(self) -> Result<Client> {
+        Client::with_config(self.config).await
+    }
+}
+
+impl Client {
+    /// Creates and initializes the client with the default options.
+    ///
+    /// The client will begin listening to items and menus and sending events immediately.
+    /// It is recommended that consumers immediately follow the call to `new` with a `subscribe` call,
+    /// then immediately follow that with a call to `items` to get the state to not miss any events.
+    ///
+    /// Menu layouts are fetched lazily.
+    /// Use [`Client::builder`] to configure this.
+    ///
+    /// The value of `service_name` must be unique on the session bus.
+    /// It is recommended to use something similar to the format of `appid-numid`,
+    /// where `numid` is a short-ish random integer.
+    ///
+    /// # Errors
+    ///
+    /// If the initialization fails for any reason,
+    /// for example if unable to connect to the bus,
+    /// this method will return an error.
+    ///
+    /// # Panics
+    ///
+    /// If the generated well-known name is invalid, the library will panic
+    /// as this indicates a major bug.
+    pub async fn new() -> Result<Self> {
+        Self::builder().build().await
+    }
+
+    /// Creates a builder for configuring the client.
+    #[must_use]
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    async fn with_config(config: Config) -> Result<Self> {
+        let (tx, rx) = broadcast::channel(config.channel_capacity);
+        let items = Arc::new(Mutex::new(HashMap::with_capacity(config.expected_items)));
+
+        let connection = Self::connect(config.bus_address.as_deref()).await?;
+        let (ctx, watcher_proxy) =
+            Self::start(connection, tx, Arc::default(), items, Arc::new(config)).await?;
+
+        // then lastly get all items
+        // it can take so long to fetch all items that we have to do this last,
+        // otherwise some incoming items get missed
+        {
+            let task_ctx = ctx.clone();
+            ctx.spawn(async move { Self::load_items(&watcher_proxy, &task_ctx).await });
+        }
+
+        let ctx = Arc::new(Mutex::new(ctx));
+        let reconnect_task = spawn(Self::reconnect_on_disconnect(Arc::downgrade(&ctx)));
+
+        debug!("tray client initialized");
+
+        Ok(Self {
+            ctx,
+            reconnect_task: reconnect_task.abort_handle(),
+            _rx: rx,
+        })
+    }
+
+    /// Waits for the connection to the bus to be lost, then reconnects,
+    /// for as long as the client exists.
+    ///
+    /// Failed attempts are retried according to [`RECONNECT_POLICY`],
+    /// as the bus daemon may take a while to come back.
+    /// If the connection cannot be watched at all, this stops without reconnecting.
+    async fn reconnect_on_disconnect(client_ctx: Weak<Mutex<Context>>) {
+        loop {
+            let Some((connection, clock)) = client_ctx.upgrade().map(|ctx| {
+                let ctx = lock(&ctx);
+                (ctx.connection.clone(), ctx.config.clock.clone())
+            }) else {
+                return;
+            };
+
+            let disconnected = wait_for_disconnect(&connection).await;
+            drop(connection);
+
+            // reconnecting while the old connection still works would reload every item,
+            // so the client stays on it without reconnecting
+            if let Err(err) = disconnected {
+                warn!("failed to watch the connection to the bus, not reconnecting: {err}");
+                return;
+            }
+
+            warn!("lost connection to the bus, reconnecting");
+
+            let mut attempt = 1;
+            loop {
+                clock.sleep(RECONNECT_POLICY.delay(attempt)).await;
+
+                let Some(ctx) = client_ctx.upgrade() else {
+                    return;
+                };
+
+                match Self::reconnect(&ctx).await {
+                    Ok(()) => break,
+                    Err(err) => {
+                        warn!("failed to reconnect to the bus: {err}");
+                        attempt = attempt.saturating_add(1);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the lost connection to the bus with a new one,
+    /// then reloads every item and sends [`Event::Reconnected`].
+    async fn reconnect(client_ctx: &Mutex<Context>) -> Result<()> {
+        let address = lock(client_ctx).config.bus_address.clone();
+        let connection = Self::connect(address.as_deref()).await?;
+        let ctx = Self::reload(client_ctx, Some(connection)).await?;
+
+        ctx.send(Event::Reconnected(snapshot(&ctx.items))).await?;
+
+        debug!("tray client reconnected");
+        Ok(())
+    }
+
+    /// Connects to the bus at `address`, or the session bus,
+    /// then starts the watcher and registers a host on it.
+    async fn connect(address: Option<&str>) -> Result<Connection> {
+        let connection = match address {
+            Some(address) => zbus::connection::Builder::address(address)?.build().await?,
+            None => Connection::session().await?,
+        };
+
+        // first start server...
+        StatusNotifierWatcher::new().attach_to(&connection).await?;
+
+        // ...then connect to it
+        let watcher_proxy = StatusNotifierWatcherProxy::new(&connection).await?;
+
+        // register a host on the watcher to declare we want to watch items
+        // get a well-known name
+        let pid = std::process::id();
+        let mut i = 0;
+        let wellknown = loop {
+            use zbus::fdo::RequestNameReply::*;
+
+            i += 1;
+            let wellknown = format!("org.freedesktop.StatusNotifierHost-{pid}-{i}");
+            let wellknown: zbus::names::WellKnownName = wellknown
+                .try_into()
+                .expect("generated well-known name is invalid");
+
+            let flags = [zbus::fdo::RequestNameFlags::DoNotQueue];
+            match connection
+                .request_name_with_flags(&wellknown, flags.into_iter().collect())
+                .await?
+            {
+                PrimaryOwner => break wellknown,
+                Exists | AlreadyOwner => {}
+                InQueue => unreachable!(
+                    "request_name_with_flags returned InQueue even though we specified DoNotQueue"
+                ),
+            };
+        };
+
+        debug!("wellknown: {wellknown}");
+        watcher_proxy
+            .register_status_notifier_host(&wellknown)
+            .await?;
+
+        Ok(connection)
+    }
+
+    /// Spawns the tasks listening for new items on `connection`.
+    ///
+    /// Returns the new context, and a proxy to the watcher for loading the existing items.
+    async fn start(
+        connection: Connection,
+        tx: broadcast::Sender<Event>,
+        subscribers: Arc<Mutex<Vec<mpsc::Sender<Event>>>>,
+        items: Arc<Mutex<State>>,
+        config: Arc<Config>,
+    ) -> Result<(Context, StatusNotifierWatcherProxy<'static>)> {
+        let watcher_proxy = StatusNotifierWatcherProxy::new(&connection).await?;
+
+        let ctx = Context {
+            connection,
+            tx,
+            subscribers,
+            items,
+            config,
+            tasks: Arc::default(),
+            paths: Arc::default(),
+            about_to_show: Arc::default(),
+            proxies: Arc::default(),
+            pending: Arc::default(),
+            stats: Arc::default(),
+            property_access: Arc::default(),
+            owners: Arc::default(),
+            capabilities: Arc::default(),
+            loaded_pixmaps: Arc::default(),
+        };
+
+        // record incoming messages before anything is subscribed,
+        // so that the fixture includes the first replies
+        if let Some(path) = &ctx.config.capture {
+            let recorder = Recorder::open(path)?;
+            let mut stream = MessageStream::from(&ctx.connection);
+
+            ctx.spawn(async move {
+                while let Some(message) = stream.next().await {
+                    recorder.record(message?)?;
+                }
+
+                Ok(())
+            });
+        }
+
+        // handle new items
+        {
+            let task_ctx = ctx.clone();
+
+            let mut stream = watcher_proxy
+                .receive_status_notifier_item_registered()
+                .await?;
+
+            ctx.spawn(async move {
+                while let Some(item) = stream.next().await {
+                    let address = item.args().map(|args| args.service);
+
+                    if let Ok(address) = address {
+                        debug!("received new item: {address}");
+
+                        // items are loaded in the background,
+                        // so that a slow item does not hold up those registered after it
+                        let address = address.to_string();
+                        let item_ctx = task_ctx.clone();
+                        task_ctx.spawn(async move { Self::handle_item(&address, item_ctx).await });
+                    }
+                }
+
+                Ok(())
+            });
+        }
+
+        // handle items the watcher dropped
+        {
+            let task_ctx = ctx.clone();
+
+            let mut stream = watcher_proxy
+                .receive_status_notifier_item_unregistered()
+                .await?;
+
+            ctx.spawn(async move {
+                while let Some(item) = stream.next().await {
+                    let Ok(args) = item.args() else {
+                        continue;
+                    };
+
+                    debug!("received item unregister: {}", args.service);
+
+                    let Ok((name, _)) = parse_address(args.service) else {
+                        continue;
+                    };
+
+                    // the name is gone if the item left the bus,
+                    // in which case its own task removes it
+                    let Ok(address) = resolve_unique_name(name, &task_ctx.connection).await else {
+                        continue;
+                    };
+
+                    task_ctx.send(Event::Remove(address)).await?;
+                }
+
+                Ok(())
+            });
+        }
+
+        // Handle other watchers unregistering and this one taking over
+        // It is necessary to clear all items as our watcher will then re-send them all
+        {
+            let task_ctx = ctx.clone();
+
+            let dbus_proxy = DBusProxy::new(&ctx.connection).await?;
+
+            let mut stream = dbus_proxy.receive_name_acquired().await?;
+
+            ctx.spawn(async move {
+                while let Some(thing) = stream.next().await {
+                    let body = thing.args()?;
+                    if body.name == names::WATCHER_BUS {
+                        let keys = lock(&task_ctx.items).keys().cloned().collect::<Vec<_>>();
+
+                        for address in keys {
+                            task_ctx.send(Event::Remove(address)).await?;
+                        }
+                    }
+                }
+
+                Ok(())
+            });
+        }
+
+        Ok((ctx, watcher_proxy))
+    }
+
+    /// Loads all items already registered on the watcher,
+    /// up to [`MAX_CONCURRENT_LOADS`] at once.
+    async fn load_items(
+        watcher_proxy: &StatusNotifierWatcherProxy<'_>,
+        ctx: &Context,
+    ) -> Result<()> {
+        let initial_items = watcher_proxy.registered_status_notifier_items().await?;
+        debug!("initial items: {initial_items:?}");
+
+        // the loads are aborted with this task when the set is dropped
+        let mut loads = JoinSet::new();
+        for item in initial_items {
+            if loads.len() >= MAX_CONCURRENT_LOADS {
+                loads.join_next().await;
+            }
+
+            let ctx = ctx.clone();
+            loads.spawn(async move {
+                if let Err(err) = Self::handle_item(&item, ctx).await {
+                    error!("{err}");
+                }
+            });
+        }
+
+        while loads.join_next().await.is_some() {}
+
+        Ok(())
+    }
+
+    /// Stops all background tasks and clears the item state,
+    /// then starts watching items again from scratch.
+    ///
+    /// This forces a full resync without dropping the client.
+    /// The event channel is kept, so existing receivers from [`Client::subscribe`] stay valid.
+    /// The bus connection is also kept, so items registered on the watcher are not lost.
+    ///
+    /// Items are reloaded from the watcher, sending [`Event::Add`] for each as normal.
+    /// Once all have loaded, [`Event::Reset`] is sent with the full set of items,
+    /// which should replace any state consumers hold.
+    ///
+    /// # Errors
+    ///
+    /// If reinitializing fails for any reason,
+    /// for example if the watcher cannot be reached,
+    /// this method will return an error.
+    /// The client is left stopped, and `restart` can be called again to retry.
+    pub async fn restart(&self) -> Result<()> {
+        let ctx = Self::reload(&self.ctx, None).await?;
+
+        ctx.send(Event::Reset(snapshot(&ctx.items))).await?;
+
+        debug!("tray client restarted");
+        Ok(())
+    }
+
+    /// Stops all background tasks and clears the item state,
+    /// then starts watching items again on `connection`, or the current connection,
+    /// and loads those already registered.
+    ///
+    /// Returns the new context, which has replaced the one in `client_ctx`.
+    async fn reload(
+        client_ctx: &Mutex<Context>,
+        connection: Option<Connection>,
+    ) -> Result<Context> {
+        let old = lock(client_ctx).clone();
+        old.abort_tasks();
+
+        lock(&old.items).clear();
+
+        let (ctx, watcher_proxy) = Self::start(
+            connection.unwrap_or(old.connection),
+            old.tx,
+            old.subscribers,
+            old.items,
+            old.config,
+        )
+        .await?;
+        *lock(client_ctx) = ctx.clone();
+
+        Self::load_items(&watcher_proxy, &ctx).await?;
+
+        Ok(ctx)
+    }
+
+    /// Gets a copy of the current context.
+    fn ctx(&self) -> Context {
+        lock(&self.ctx).clone()
+    }
+
+    /// Processes an incoming item to send the initial add event,
+    /// then set up listeners for it and its menu.
+    ///
+    /// An item registered again while it is still loading is skipped.
+    async fn handle_item(address: &str, ctx: Context) -> crate::error::Result<()> {
+        let Some(_pending) = PendingItem::start(&ctx, address) else {
+            debug!("{address} is already loading, skipping");
+            return Ok(());
+        };
+
+        let (registered_name, path) = match parse_address(address) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                warn!("rejecting item registration: {err}");
+                return Ok(());
+            }
+        };
+
+        // items are always keyed on their unique name,
+        // so that the disconnect match below sees the same name the item was stored under.
+        let destination = &resolve_unique_name(registered_name, &ctx.connection).await?;
+
+        let debug = ctx
+            .config
+            .debug_destination
+            .as_deref()
+            .is_some_and(|name| name == registered_name || name == destination);
+
+        let properties_proxy = PropertiesProxy::builder(&ctx.connection)
+            .destination(destination.to_string())?
+            .path(path.clone())?
+            .build()
+            .await?;
+
+        // some applications register the same item repeatedly,
+        // which only needs the existing item brought up to date
+        let same_path = lock(&ctx.paths).get(destination) == Some(&path);
+        let existing = lock(&ctx.items)
+            .get(destination)
+            .map(|(item, _)| item.clone());
+        match existing {
+            Some(existing) if same_path => {
+                debug!("{destination}{path} registered again, refreshing it");
+                let (mut properties, _, _) = with_retries(&ctx.config, "GetAll", || {
+                    Self::get_item_properties(
+                        destination,
+                        &path,
+                        &properties_proxy,
+                        ctx.config.lazy_pixmaps,
+                        debug,
+                        &ctx,
+                    )
+                })
+                .await?;
+
+                properties.fix_pixmap_byte_order(ctx.config.swap_pixmap_byte_order(&properties.id));
+                return Self::refresh_item(destination, &existing, properties, debug, &ctx).await;
+            }
+            // the item now lives at another path, so it is removed and watched from scratch
+            Some(_) => {
+                debug!("{destination} registered again at {path}, replacing it");
+                ctx.abort_item_tasks(destination);
+                ctx.send(Event::Remove(destination.to_string())).await?;
+            }
+            None => {}
+        }
+
+        // signals are subscribed to before the properties are read,
+        // so that no change made in between is missed
+        let well_known_name = (registered_name != destination).then(|| registered_name.to_string());
+        let signals = Self::subscribe_item(
+            destination,
+            &path,
+            well_known_name.as_deref(),
+            &properties_proxy,
+            &ctx,
+        )
+        .await?;
+
+        let (mut properties, skipped, access) = with_retries(&ctx.config, "GetAll", || {
+            Self::get_item_properties(
+                destination,
+                &path,
+                &properties_proxy,
+                ctx.config.lazy_pixmaps,
+                debug,
+                &ctx,
+            )
+        })
+        .await?;
+
+        lock(&ctx.paths).insert(destination.to_string(), path.clone());
+        if access != PropertyAccess::Properties {
+            lock(&ctx.property_access).insert(destination.to_string(), access);
+        }
+
+        properties.fix_pixmap_byte_order(ctx.config.swap_pixmap_byte_order(&properties.id));
+
+        if let Some(replaced) = Self::find_replaced_item(destination, &properties.id, &ctx).await {
+            debug!("{destination} replaces {replaced}");
+
+            ctx.send(Event::Remove(replaced)).await?;
+        }
+
+        let capabilities = if ctx.config.introspect_items {
+            Self::probe_capabilities(destination, &path, properties.menu.as_deref(), &ctx).await
+        } else {
+            None
+        };
+        let has_menu = capabilities
+            .as_ref()
+            .is_none_or(|capabilities| capabilities.menu);
+        if let Some(capabilities) = capabilities {
+            lock(&ctx.capabilities).insert(destination.to_string(), capabilities);
+        }
+
+        ctx.send(Event::Add(
+            destination.to_string(),
+            properties.clone().into(),
+        ))
+        .await?;
+
+        for (name, err) in skipped {
+            ctx.send(Event::Warning(
+                destination.to_string(),
+                Warning::InvalidProperty {
+                    name: name.to_string(),
+                    message: err.to_string(),
+                },
+            ))
+            .await?;
+        }
+
+        {
+            let task_ctx = ctx.clone();
+            let task_destination = destination.to_string();
+
+            ctx.spawn_for_item(destination, async move {
+                let destination = task_destination;
+                Self::watch_item_properties(
+                    &destination,
+                    &path,
+                    well_known_name,
+                    properties_proxy,
+                    signals,
+                    debug,
+                    task_ctx,
+                )
+                .await?;
+
+                debug!("Stopped watching {destination}{path}");
+                Ok(())
+            });
+        }
+
+        match properties.menu {
+            Some(menu) if has_menu => {
+                Self::connect_menu(destination, menu, ctx.config.prefetch_menus, debug, &ctx)
+                    .await?;
+            }
+            Some(menu) => debug!("[{destination}] {menu} does not implement the menu interface"),
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Introspects the item at `destination`, and its menu at `menu_path` if it has one,
+    /// to find what they implement.
+    ///
+    /// Returns `None` if either could not be introspected.
+    async fn probe_capabilities(
+        destination: &str,
+        path: &str,
+        menu_path: Option<&str>,
+        ctx: &Context,
+    ) -> Option<Capabilities> {
+        let introspect = |path: String| async move {
+            let proxy = IntrospectableProxy::builder(&ctx.connection)
+                .destination(destination.to_string())?
+                .path(path)?
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await?;
+
+            let xml = ctx
+                .call(destination, "Introspect", proxy.introspect())
+                .await?;
+            Ok::<_, Error>(parse_interfaces(&xml))
+        };
+
+        let item = match introspect(path.to_string()).await {
+            Ok(interfaces) => interfaces,
+            Err(err) => {
+                debug!("[{destination}{path}] failed to introspect: {err}");
+                return None;
+            }
+        };
+
+        let (interface, members) = item
+            .into_iter()
+            .find(|(name, _)| name.ends_with(".StatusNotifierItem"))
+            .unzip();
+        let members = members.unwrap_or_default();
+
+        let menu_methods = match menu_path {
+            Some(menu_path) => match introspect(menu_path.to_string()).await {
+                Ok(mut interfaces) => interfaces
+                    .remove(MENU_INTERFACE)
+                    .map(|members| members.methods),
+                Err(err) => {
+                    debug!("[{destination}{menu_path}] failed to introspect: {err}");
+                    return None;
+                }
+            },
+            None => None,
+        };
+
+        Some(Capabilities {
+            interface,
+            menu: menu_methods.is_some(),
+            methods: members.methods,
+            signals: members.signals,
+            menu_methods: menu_methods.unwrap_or_default(),
+        })
+    }
+
+    /// Sends updates for each property of the item at `destination`
+    /// which differs from those it was registered with before,
+    /// reconnecting its menu if that moved.
+    async fn refresh_item(
+        destination: &str,
+        old: &StatusNotifierItem,
+        new: StatusNotifierItem,
+        debug: bool,
+        ctx: &Context,
+    ) -> Result<()> {
+        // pixmaps are not read with the other properties when loaded lazily
+        let updates = state::item_updates(old, &new)
+            .into_iter()
+            .filter(|update| !(ctx.config.lazy_pixmaps && is_pixmap_update(update)));
+
+        for update in updates {
+            ctx.send(Event::Update(destination.to_string(), update))
+                .await?;
+        }
+
+        match new.menu {
+            Some(menu) if old.menu.as_ref() != Some(&menu) => {
+                Self::connect_menu(destination, menu, ctx.config.prefetch_menus, debug, ctx).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends the menu connect event for the item at `destination`,
+    /// then starts watching the menu if menus are tracked.
+    ///
+    /// If `prefetch` is set, the layout is fetched immediately.
+    async fn connect_menu(
+        destination: &str,
+        menu: String,
+        prefetch: bool,
+        debug: bool,
+        ctx: &Context,
+    ) -> Result<()> {
+        ctx.send(Event::Update(
+            destination.to_string(),
+            UpdateEvent::MenuConnect(menu.clone()),
+        ))
+        .await?;
+
+        if ctx.config.track_menus && prefetch {
+            let (proxy, _) = ctx.menu_proxy(destination, &menu).await?;
+            let task_ctx = ctx.clone();
+            let task_destination = destination.to_string();
+
+            ctx.spawn_for_menu(destination, async move {
+                Self::watch_menu(task_destination, proxy, prefetch, debug, task_ctx).await
+            });
+        } else {
+            // otherwise the menu is watched from its first use, see `Client::get_menu_proxy`
+            ctx.abort_menu_task(destination);
+        }
+
+        Ok(())
+    }
+
+    /// Reconnects to the menu of the item at `destination` if its path changed.
+    ///
+    /// The new path is taken from `menu` if the change signal carried it,
+    /// or else the `Menu` property is read again.
+    async fn refresh_menu_path(
+        destination: &str,
+        menu: Option<String>,
+        properties_proxy: &PropertiesProxy<'_>,
+        debug: bool,
+        ctx: &Context,
+    ) -> Result<()> {
+        let menu = match menu {
+            Some(menu) => menu,
+            None => {
+                let get = properties_proxy.get(
+                    InterfaceName::from_static_str(PROPERTIES_INTERFACE)
+                        .expect("to be valid interface name"),
+                    "Menu",
+                );
+                let menu = ctx.call(destination, "Get Menu", get).await?;
+
+                menu.downcast_ref::<ObjectPath>()?.to_string()
+            }
+        };
+
+        let current = lock(&ctx.items)
+            .get(destination)
+            .and_then(|(item, _)| item.menu.clone());
+
+        if current.as_deref() != Some(menu.as_str()) {
+            debug!("[{destination}] menu moved to {menu}");
+            Self::connect_menu(destination, menu, true, debug, ctx).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Finds a previous registration of the item now at `destination`,
+    /// for applications which re-register under a new bus name.
+    ///
+    /// An existing item is considered the same application if it has the same `id`, and either:
+    ///
+    /// - its bus name no longer has an owner,
+    ///   because the disconnect has not been processed yet, or
+    /// - its bus name is owned by the same process.
+    ///
+    /// A matching `id` alone is not enough,
+    /// as unrelated applications built on the same toolkit often share one.
+    async fn find_replaced_item(destination: &str, id: &str, ctx: &Context) -> Option<String> {
+        let candidates = lock(&ctx.items)
+            .iter()
+            .filter(|(address, (item, _))| item.id == id && *address != destination)
+            .map(|(address, _)| address.clone())
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let dbus_proxy = DBusProxy::new(&ctx.connection).await.ok()?;
+        let new_process = process_id(&dbus_proxy, destination).await;
+
+        for candidate in candidates {
+            let Ok(name) = BusName::try_from(candidate.as_str()) else {
+                continue;
+            };
+
+            let has_owner = dbus_proxy.name_has_owner(name).await.unwrap_or(true);
+
+            if !has_owner
+                || (new_process.is_some()
+                    && process_id(&dbus_proxy, &candidate).await == new_process)
+            {
+                return Some(candidate);
+            }
+        }
+
+        None
+    }
+
+    /// Gets the properties for an SNI item,
+    /// along with any properties which were skipped as they could not be parsed,
+    /// and how the properties were read.
+    ///
+    /// Items which fail `GetAll`, or answer it without an id, have each property fetched,
+    /// and if that was seen before the item skips `GetAll` altogether.
+    /// Items which do not implement the properties interface
+    /// are given only an id, taken from their address.
+    async fn get_item_properties(
+        destination: &str,
+        path: &str,
+        properties_proxy: &PropertiesProxy<'_>,
+        lazy_pixmaps: bool,
+        debug: bool,
+        ctx: &Context,
+    ) -> crate::error::Result<(
+        StatusNotifierItem,
+        Vec<(&'static str, Error)>,
+        PropertyAccess,
+    )> {
+        let interface = InterfaceName::from_static_str(PROPERTIES_INTERFACE)
+            .expect("to be valid interface name");
+
+        let names = [NON_PIXMAP_PROPERTIES, PIXMAP_PROPERTIES].concat();
+        let mut access = ctx.property_access(destination);
+
+        let properties = if lazy_pixmaps {
+            // the id is fetched on its own first,
+            // as the error is needed if it fails where the others are skipped
+            let get_id = properties_proxy.get(interface, "Id");
+            match ctx.call(destination, "Get Id", get_id).await {
+                Ok(id) => {
+                    let names = &NON_PIXMAP_PROPERTIES[1..];
+                    let mut properties = get_properties(properties_proxy, names, ctx).await;
+                    properties.insert("Id".to_string(), id);
+                    Ok(properties)
+                }
+                Err(err) => Err(err),
+            }
+        } else if access == PropertyAccess::Get {
+            Ok(get_properties(properties_proxy, &names, ctx).await)
+        } else {
+            let get_all = properties_proxy.get_all(interface);
+
+            // a hung item times out as unresponsive, so is not asked for each property below
+            match ctx.call(destination, "GetAll", get_all).await {
+                // some items fail `GetAll` because of a single bad property,
+                // so the properties are fetched one by one to get the rest
+                Err(Error::ZBusFdo(err))
+                    if !is_unresponsive(&err) && !lacks_properties_interface(&err) =>
+                {
+                    warn!(
+                        "[{destination}{path}] GetAll failed, \
+                         fetching properties one by one: {err}"
+                    );
+
+                    let properties = get_properties(properties_proxy, &names, ctx).await;
+                    if properties.is_empty() {
+                        Err(err.into())
+                    } else {
+                        access = PropertyAccess::Get;
+                        Ok(properties)
+                    }
+                }
+                // some items answer `GetAll` with nothing, but answer `Get`
+                Ok(properties) if !properties.contains_key("Id") => {
+                    warn!(
+                        "[{destination}{path}] GetAll returned no id, \
+                         fetching properties one by one"
+                    );
+
+                    access = PropertyAccess::Get;
+                    Ok(get_properties(properties_proxy, &names, ctx).await)
+                }
+                properties => properties,
+            }
+        };
+
+        let (properties, access) = match properties {
+            Ok(properties) => (properties, access),
+            // the item can still be shown, and updated from signals carrying values
+            Err(Error::ZBusFdo(err)) if lacks_properties_interface(&err) => {
+                warn!(
+                    "[{destination}{path}] item does not implement the properties interface, \
+                     only its address is known: {err}"
+                );
+
+                let id = OwnedValue::from(Str::from(destination.to_string()));
+                (
+                    HashMap::from([("Id".to_string(), id)]),
+                    PropertyAccess::None,
+                )
+            }
+            Err(err) => {
+                error!("Error fetching properties from {destination}{path}: {err:?}");
+                return Err(err);
+            }
+        };
+
+        if debug {
+            debug!(target: RAW_TARGET, "[{destination}{path}] GetAll: {properties:?}");
+        }
+
+        let (item, skipped) = StatusNotifierItem::from_props(&DBusProps(properties))?;
+        Ok((item, skipped, access))
+    }
+
+    /// Watches an SNI item's properties,
+    /// sending an update event whenever they change.
+    ///
+    /// Items registered with a well-known name follow it to the connection
+    /// which takes it over, such as a new instance of the application,
+    /// see [`Client::transfer_item`].
+    async fn watch_item_properties(
+        destination: &str,
+        path: &str,
+        well_known_name: Option<String>,
+        mut properties_proxy: PropertiesProxy<'static>,
+        mut signals: ItemSignals,
+        debug: bool,
+        ctx: Context,
+    ) -> crate::error::Result<()> {
+        loop {
+            let watch =
+                Self::watch_item_owner(destination, path, signals, &properties_proxy, debug, &ctx);
+
+            let Some(owner) = watch.await? else {
+                return Ok(());
+            };
+
+            let transfer = Self::transfer_item(
+                destination,
+                path,
+                owner,
+                well_known_name.as_deref(),
+                debug,
+                &ctx,
+            );
+            (properties_proxy, signals) = transfer.await?;
+        }
+    }
+
+    /// Subscribes to the signals of the item at `destination` from its current owner,
+    /// and to owner changes of `well_known_name` if it registered with one.
+    async fn subscribe_item(
+        destination: &str,
+        path: &str,
+        well_known_name: Option<&str>,
+        properties_proxy: &PropertiesProxy<'_>,
+        ctx: &Context,
+    ) -> Result<ItemSignals> {
+        let owner = ctx.owner(destination);
+
+        // items announce changes with `New*` signals rather than `PropertiesChanged`,
+        // so the zbus property cache would go stale, and filling it costs a `GetAll`
+        let notifier_item_proxy = StatusNotifierItemProxy::builder(&ctx.connection)
+            .destination(owner.as_str())?
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+
+        let dbus_proxy = DBusProxy::new(&ctx.connection).await?;
+
+        // match rules are narrowed to the signals of this item,
+        // so that the bus daemon does not send every owner or property change on the bus
+        let disconnect = dbus_proxy
+            .receive_name_owner_changed_with_args(&[(0, owner.as_str())])
+            .await?;
+        let transfer = match well_known_name {
+            Some(name) => Some(
+                dbus_proxy
+                    .receive_name_owner_changed_with_args(&[(0, name)])
+                    .await?,
+            ),
+            None => None,
+        };
+        let props_changed = notifier_item_proxy.inner().receive_all_signals().await?;
+
+        // there is no `New*` signal for the menu,
+        // so the standard properties signal is the only way to hear of it moving
+        let standard_props_changed = properties_proxy
+            .receive_properties_changed_with_args(&[(0, PROPERTIES_INTERFACE)])
+            .await?;
+
+        Ok(ItemSignals {
+            props_changed,
+            standard_props_changed,
+            disconnect,
+            transfer,
+        })
+    }
+
+    /// Watches an SNI item's properties while its current owner has it,
+    /// returning the new owner if its well-known name is taken over by another connection.
+    async fn watch_item_owner(
+        destination: &str,
+        path: &str,
+        signals: ItemSignals,
+        properties_proxy: &PropertiesProxy<'_>,
+        debug: bool,
+        ctx: &Context,
+    ) -> crate::error::Result<Option<String>> {
+        let owner = ctx.owner(destination);
+        let ItemSignals {
+            mut props_changed,
+            mut standard_props_changed,
+            mut disconnect,
+            mut transfer,
+        } = signals;
+
+        let mut liveness = ctx
+            .config
+            .liveness_interval
+            .map(|period| liveness_timer(destination, period, &ctx.config.clock));
 
-        let mut disconnect_stream = dbus_proxy.receive_name_owner_changed().await?;
-        let mut props_changed = notifier_item_proxy.inner().receive_all_signals().await?;
+        let mut idle_ping = ctx
+            .config
+            .idle_ping_interval
+            .map(|period| liveness_timer(destination, period, &ctx.config.clock));
+        let mut last_signal = ctx.config.clock.now();
+
+        let mut poll = ctx
+            .config
+            .poll_interval
+            .map(|period| liveness_timer(destination, period, &ctx.config.clock));
+        let mut signalled = false;
 
         loop {
             tokio::select! {
                 Some(change) = props_changed.next() => {
-                    match Self::get_update_event(change, &properties_proxy).await {
-                        Ok(Some(event)) => {
-                                debug!("[{destination}{path}] received property change: {event:?}");
-                                tx.send(Event::Update(destination.to_string(), event))?;
-                            }
-                        Err(e) => {
-                            error!("Error parsing update properties from {destination}{path}: {e:?}");
+                    last_signal = ctx.config.clock.now();
+                    signalled = true;
+
+                    // signals often arrive in bursts, so take any already buffered
+                    // to avoid fetching the same property repeatedly
+                    let mut changes = vec![change];
+                    while changes.len() < MAX_SIGNAL_BATCH {
+                        let Some(Some(change)) = poll_once(props_changed.next()).await else {
+                            break;
+                        };
+                        changes.push(change);
+                    }
+
+                    if debug {
+                        for change in &changes {
+                            log_raw_signal(destination, change);
+                        }
+                    }
+
+                    let started = Instant::now();
+                    let signals = changes.len();
+                    for change in coalesce_signals(changes) {
+                        Self::handle_item_signal(
+                            &change,
+                            destination,
+                            path,
+                            properties_proxy,
+                            debug,
+                            ctx,
+                        )
+                        .await?;
+                    }
+
+                    ctx.record_signals(destination, signals, started);
+                }
+                Some(change) = standard_props_changed.next() => {
+                    last_signal = ctx.config.clock.now();
+                    signalled = true;
+
+                    let args = change.args()?;
+                    let menu = args
+                        .changed_properties()
+                        .get("Menu")
+                        .and_then(|menu| menu.downcast_ref::<ObjectPath>().ok())
+                        .map(|menu| menu.to_string());
+
+                    let menu_changed = args.interface_name() == PROPERTIES_INTERFACE
+                        && (menu.is_some() || args.invalidated_properties().contains(&"Menu"));
+
+                    if menu_changed {
+                        let refresh = Self::refresh_menu_path(
+                            destination,
+                            menu,
+                            properties_proxy,
+                            debug,
+                            ctx,
+                        );
+
+                        if let Err(err) = refresh.await {
+                            error!("[{destination}{path}] failed to refresh menu path: {err}");
                         }
-                        _ => {}
                     }
                 }
-                Some(signal) = disconnect_stream.next() => {
+                Some(signal) = disconnect.next() => {
                     let args = signal.args()?;
                     let old = args.old_owner();
                     let new = args.new_owner();
 
                     if let (Some(old), None) = (old.as_ref(), new.as_ref()) {
-                        if old == destination {
+                        if old.as_str() == owner {
                             debug!("[{destination}{path}] disconnected");
 
-                            let watcher_proxy = StatusNotifierWatcherProxy::new(connection)
-                                .await
-                                .expect("Failed to open StatusNotifierWatcherProxy");
+                            Self::remove_item(destination, ctx).await?;
+                            break Ok(None);
+                        }
+                    }
+                }
+                Some(signal) = next_in(&mut transfer) => {
+                    let args = signal.args()?;
 
-                            if let Err(error) = watcher_proxy.unregister_status_notifier_item(old).await {
-                                error!("{error:?}");
-                            }
+                    let new = args.new_owner().as_ref().filter(|new| new.as_str() != owner);
+                    if let Some(new) = new {
+                        debug!("[{destination}{path}] {} moved to {new}", args.name());
+                        break Ok(Some(new.to_string()));
+                    }
+                }
+                () = next_tick(&mut liveness) => {
+                    let status = properties_proxy.get(
+                        InterfaceName::from_static_str(PROPERTIES_INTERFACE)
+                            .expect("to be valid interface name"),
+                        "Status",
+                    );
 
-                            items.lock().expect("mutex lock should succeed").remove(&destination.to_string());
+                    let unresponsive =
+                        match clock::timeout(&*ctx.config.clock, LIVENESS_TIMEOUT, status).await {
+                            Some(Ok(_)) => false,
+                            Some(Err(err)) => is_unresponsive(&err),
+                            None => true,
+                        };
 
-                            tx.send(Event::Remove(destination.to_string()))?;
-                            break Ok(());
-                        }
+                    if unresponsive {
+                        warn!("[{destination}{path}] failed liveness check, removing");
+
+                        Self::remove_item(destination, ctx).await?;
+                        break Ok(None);
+                    }
+                }
+                () = next_tick(&mut idle_ping) => {
+                    let idle = ctx.config.idle_ping_interval.unwrap_or_default();
+                    if ctx.config.clock.elapsed(last_signal) < idle {
+                        continue;
+                    }
+
+                    let limit = ctx.call_timeout(destination);
+                    if Self::ping(&owner, path, limit, ctx).await? {
+                        warn!("[{destination}{path}] is no longer on the bus, removing");
+
+                        Self::remove_item(destination, ctx).await?;
+                        break Ok(None);
+                    }
+                }
+                () = next_tick(&mut poll) => {
+                    if signalled && !ctx.lacks_signals(destination) {
+                        continue;
+                    }
+
+                    let poll = Self::poll_item(destination, path, properties_proxy, debug, ctx);
+                    if let Err(err) = poll.await {
+                        debug!("[{destination}{path}] failed to poll properties: {err}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the properties of the item at `destination` again,
+    /// sending updates for any which changed,
+    /// for items which do not announce their changes.
+    async fn poll_item(
+        destination: &str,
+        path: &str,
+        properties_proxy: &PropertiesProxy<'_>,
+        debug: bool,
+        ctx: &Context,
+    ) -> Result<()> {
+        let (mut properties, _, _) = Self::get_item_properties(
+            destination,
+            path,
+            properties_proxy,
+            ctx.config.lazy_pixmaps,
+            debug,
+            ctx,
+        )
+        .await?;
+
+        properties.fix_pixmap_byte_order(ctx.config.swap_pixmap_byte_order(&properties.id));
+
+        let existing = lock(&ctx.items)
+            .get(destination)
+            .map(|(item, _)| item.clone());
+        match existing {
+            Some(existing) => {
+                Self::refresh_item(destination, &existing, properties, debug, ctx).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Moves the item at `destination` to `owner`, the connection which took over its name,
+    /// returning a proxy for its properties there and the signals subscribed to from it.
+    ///
+    /// The properties are read again and sent as updates,
+    /// and its menu is reconnected, as an application replaced by a new instance
+    /// does not necessarily register again.
+    async fn transfer_item(
+        destination: &str,
+        path: &str,
+        owner: String,
+        well_known_name: Option<&str>,
+        debug: bool,
+        ctx: &Context,
+    ) -> Result<(PropertiesProxy<'static>, ItemSignals)> {
+        lock(&ctx.owners).insert(destination.to_string(), owner.clone());
+        lock(&ctx.proxies).remove(destination);
+
+        let properties_proxy = PropertiesProxy::builder(&ctx.connection)
+            .destination(owner)?
+            .path(path.to_string())?
+            .build()
+            .await?;
+
+        let signals =
+            Self::subscribe_item(destination, path, well_known_name, &properties_proxy, ctx)
+                .await?;
+
+        let (mut properties, _, access) = Self::get_item_properties(
+            destination,
+            path,
+            &properties_proxy,
+            ctx.config.lazy_pixmaps,
+            debug,
+            ctx,
+        )
+        .await?;
+
+        if access == PropertyAccess::Properties {
+            lock(&ctx.property_access).remove(destination);
+        } else {
+            lock(&ctx.property_access).insert(destination.to_string(), access);
+        }
+
+        properties.fix_pixmap_byte_order(ctx.config.swap_pixmap_byte_order(&properties.id));
+
+        let existing = lock(&ctx.items)
+            .get(destination)
+            .map(|(item, _)| item.clone());
+        let Some(existing) = existing else {
+            return Ok((properties_proxy, signals));
+        };
+
+        // a menu which moved is reconnected with the other updates,
+        // but one at the same path is still watched on the previous owner
+        let menu = properties.menu.clone();
+        Self::refresh_item(destination, &existing, properties, debug, ctx).await?;
+        if let Some(menu) = menu.filter(|menu| existing.menu.as_ref() == Some(menu)) {
+            Self::connect_menu(destination, menu, true, debug, ctx).await?;
+        }
+
+        Ok((properties_proxy, signals))
+    }
+
+    /// Pings the item at `destination`,
+    /// returning whether the bus reported that nothing owns its name any more.
+    ///
+    /// Pings which time out or fail for other reasons are only logged,
+    /// as the item may still be there.
+    async fn ping(destination: &str, path: &str, limit: Duration, ctx: &Context) -> Result<bool> {
+        let peer_proxy = PeerProxy::builder(&ctx.connection)
+            .destination(destination)?
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+
+        match clock::timeout(&*ctx.config.clock, limit, peer_proxy.ping()).await {
+            Some(Ok(())) => Ok(false),
+            Some(Err(err)) if is_disconnected(&err) => Ok(true),
+            Some(Err(err)) => {
+                debug!("[{destination}{path}] ping failed: {err}");
+                Ok(false)
+            }
+            None => {
+                debug!("[{destination}{path}] ping timed out");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Handles a signal from an SNI item,
+    /// sending an update event for the property it announced a change to.
+    async fn handle_item_signal(
+        change: &Message,
+        destination: &str,
+        path: &str,
+        properties_proxy: &PropertiesProxy<'_>,
+        debug: bool,
+        ctx: &Context,
+    ) -> Result<()> {
+        let header = change.header();
+        let Some(member) = header.member() else {
+            warn!("[{destination}{path}] update message header missing `member`");
+            return Ok(());
+        };
+
+        let get_update_event = with_retries(&ctx.config, member.as_str(), || {
+            Self::get_update_event(change, member.as_str(), properties_proxy, debug, ctx)
+        });
+
+        match get_update_event.await {
+            Ok(Some(event)) => {
+                debug!("[{destination}{path}] received property change: {event:?}");
+
+                let mut events = vec![event];
+                if let Some(pixmaps) = Self::get_pixmap_update_event(
+                    member.as_str(),
+                    destination,
+                    properties_proxy,
+                    debug,
+                    ctx,
+                )
+                .await
+                {
+                    events.push(pixmaps);
+                }
+
+                for event in events {
+                    let unchanged = lock(&ctx.items)
+                        .get(destination)
+                        .is_some_and(|(item, _)| state::is_unchanged_icon(item, &event));
+
+                    if unchanged {
+                        trace!("[{destination}{path}] skipping unchanged icon: {event:?}");
+                    } else {
+                        ctx.send(Event::Update(destination.to_string(), event))
+                            .await?;
                     }
                 }
             }
+            Err(err @ (Error::ZBusVariant(_) | Error::InvalidData(_))) => {
+                let name = property_name_for_signal(member.as_str()).unwrap_or(member.as_str());
+                warn!("[{destination}{path}] skipping invalid property {name}: {err}");
+
+                ctx.send(Event::Warning(
+                    destination.to_string(),
+                    Warning::InvalidProperty {
+                        name: name.to_string(),
+                        message: err.to_string(),
+                    },
+                ))
+                .await?;
+            }
+            Err(e) => {
+                error!("Error fetching update properties from {destination}{path}: {e:?}");
+
+                let name = property_name_for_signal(member.as_str()).unwrap_or(member.as_str());
+                ctx.send(Event::Warning(
+                    destination.to_string(),
+                    Warning::CallFailed {
+                        method: format!("Get {name}"),
+                        message: e.to_string(),
+                    },
+                ))
+                .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Unregisters the item at `destination` from the watcher,
+    /// then removes it from the state and sends the remove event.
+    ///
+    /// Nothing is sent if the item has already been removed,
+    /// for example because it was replaced.
+    async fn remove_item(destination: &str, ctx: &Context) -> Result<()> {
+        if !lock(&ctx.items).contains_key(destination) {
+            debug!("{destination} has already been removed");
+            return Ok(());
+        }
+
+        match StatusNotifierWatcherProxy::new(&ctx.connection).await {
+            Ok(watcher_proxy) => {
+                if let Err(error) = watcher_proxy
+                    .unregister_status_notifier_item(destination)
+                    .await
+                {
+                    error!("{error:?}");
+                }
+            }
+            Err(error) => {
+                error!("failed to open watcher proxy to unregister {destination}: {error}")
+            }
         }
+
+        ctx.send(Event::Remove(destination.to_string())).await?;
+        Ok(())
     }
 
-    /// Gets the update event for a `DBus` properties change message.
+    /// Gets the update event for an SNI `New*` signal `member`.
+    ///
+    /// Signals carrying the new value are used as-is,
+    /// otherwise the property is fetched.
     async fn get_update_event(
-        change: Message,
+        change: &Message,
+        member: &str,
         properties_proxy: &PropertiesProxy<'_>,
+        debug: bool,
+        ctx: &Context,
     ) -> Result<Option<UpdateEvent>> {
-        let header = change.header();
-        let member = header
-            .member()
-            .ok_or(Error::InvalidData("Update message header missing `member`"))?;
-
-        let property_name = match member.as_str() {
-            "NewAttentionIcon" => "AttentionIconName",
-            "NewIcon" => "IconName",
-            "NewOverlayIcon" => "OverlayIconName",
-            "NewStatus" => "Status",
-            "NewTitle" => "Title",
-            "NewToolTip" => "ToolTip",
-            _ => &member.as_str()["New".len()..],
+        if let Some(event) = update_event_from_signal(change, member) {
+            debug!("received tray item update: {member} -> {event:?}");
+            return Ok(Some(event));
+        }
+
+        let Some(property_name) = property_name_for_signal(member) else {
+            warn!("received unhandled update event: {member}");
+            return Ok(None);
         };
 
-        let property = properties_proxy
-            .get(
-                InterfaceName::from_static_str(PROPERTIES_INTERFACE)
-                    .expect("to be valid interface name"),
-                property_name,
-            )
+        let destination = &ctx.address(properties_proxy.inner().destination());
+        if ctx.property_access(destination) == PropertyAccess::None {
+            debug!("{destination} has no properties to fetch for {member}");
+            return Ok(None);
+        }
+
+        let get = properties_proxy.get(
+            InterfaceName::from_static_str(PROPERTIES_INTERFACE)
+                .expect("to be valid interface name"),
+            property_name,
+        );
+        let property = ctx
+            .call(destination, &format!("Get {property_name}"), get)
             .await?;
 
+        if debug {
+            debug!(
+                target: RAW_TARGET,
+                "[{}{}] Get {property_name}: {property:?}",
+                properties_proxy.inner().destination(),
+                properties_proxy.inner().path()
+            );
+        }
+
         debug!("received tray item update: {member} -> {property:?}");
 
-        use UpdateEvent::*;
-        Ok(match member.as_str() {
-            "NewAttentionIcon" => Some(AttentionIcon(property.to_string().ok())),
-            "NewIcon" => Some(Icon(property.to_string().ok())),
-            "NewOverlayIcon" => Some(OverlayIcon(property.to_string().ok())),
-            "NewStatus" => Some(Status(
-                property.downcast_ref::<&str>().map(item::Status::from)?,
-            )),
-            "NewTitle" => Some(Title(property.to_string().ok())),
-            "NewToolTip" => Some(Tooltip({
-                property
-                    .downcast_ref::<&Structure>()
-                    .ok()
-                    .map(crate::item::Tooltip::try_from)
-                    .transpose()?
-            })),
-            _ => {
-                warn!("received unhandled update event: {member}");
+        update_event_from_property(member, &property)
+    }
+
+    /// Gets the update event for the pixmap announced by an icon signal `member`.
+    ///
+    /// If pixmaps are loaded lazily, this is skipped until the pixmap has been loaded.
+    /// Items without the pixmap property are skipped too.
+    async fn get_pixmap_update_event(
+        member: &str,
+        destination: &str,
+        properties_proxy: &PropertiesProxy<'_>,
+        debug: bool,
+        ctx: &Context,
+    ) -> Option<UpdateEvent> {
+        let (property_name, to_event) = pixmap_property_for_signal(member)?;
+
+        let (has_pixmap, id) = lock(&ctx.items).get(destination).map(|(item, _)| {
+            let pixmap = match property_name {
+                "IconPixmap" => &item.icon_pixmap,
+                "OverlayIconPixmap" => &item.overlay_icon_pixmap,
+                _ => &item.attention_icon_pixmap,
+            };
+            (pixmap.is_some(), item.id.clone())
+        })?;
+
+        if ctx.config.lazy_pixmaps && !has_pixmap {
+            // the pixmap may have been added, so let `Client::load_icon` fetch it again
+            if let Some(loaded) = lock(&ctx.loaded_pixmaps).get_mut(destination) {
+                loaded.remove(property_name);
+            }
+            return None;
+        }
+
+        if ctx.property_access(destination) == PropertyAccess::None {
+            return None;
+        }
+
+        let properties = get_properties(properties_proxy, &[property_name], ctx).await;
+
+        if debug {
+            debug!(
+                target: RAW_TARGET,
+                "[{}{}] Get {property_name}: {:?}",
+                properties_proxy.inner().destination(),
+                properties_proxy.inner().path(),
+                properties.get(property_name)
+            );
+        }
+
+        match DBusProps(properties)
+            .get_icon_pixmap(property_name)
+            .transpose()
+        {
+            Ok(mut pixmaps) => {
+                if let Some(pixmaps) = &mut pixmaps {
+                    IconPixmap::fix_byte_order(pixmaps, ctx.config.swap_pixmap_byte_order(&id));
+                }
+                Some(to_event(pixmaps))
+            }
+            Err(err) => {
+                warn!("[{destination}] skipping invalid property {property_name}: {err}");
                 None
             }
-        })
+        }
     }
 
     /// Watches the `DBusMenu` associated with an SNI item.
     ///
-    /// This gets the initial menu, sending an update event immediately.
+    /// If `prefetch` is set, this gets the initial menu, sending an update event immediately.
     /// Update events are then sent for any further updates
-    /// until the item is removed.
+    /// until the item is removed or its menu moves.
+    ///
+    /// The revision of the last layout fetched is tracked.
+    /// Layout updates for older revisions are skipped,
+    /// and property updates for items missing from the cached layout,
+    /// which mean an update was missed, fetch the whole layout again.
     async fn watch_menu(
         destination: String,
-        menu_path: &str,
-        connection: &Connection,
-        tx: broadcast::Sender<Event>,
-        items: Arc<Mutex<State>>,
+        dbus_menu_proxy: DBusMenuProxy<'static>,
+        prefetch: bool,
+        debug: bool,
+        ctx: Context,
     ) -> crate::error::Result<()> {
-        let dbus_menu_proxy = DBusMenuProxy::builder(connection)
-            .destination(destination.as_str())?
-            .path(menu_path)?
-            .build()
-            .await?;
+        let menu_path = dbus_menu_proxy.inner().path().to_string();
 
-        let menu = dbus_menu_proxy.get_layout(0, 10, &[]).await?;
-        let menu = TrayMenu::try_from(menu)?;
+        let mut revision = None;
 
-        if let Some((_, menu_cache)) = items
-            .lock()
-            .expect("mutex lock should succeed")
-            .get_mut(&destination)
-        {
-            menu_cache.replace(menu.clone());
-        } else {
-            error!("could not find item in state");
+        if prefetch {
+            let menu = fetch_menu(&dbus_menu_proxy, &destination, &ctx).await?;
+            revision = Some(menu.id);
+            ctx.send(Event::Update(
+                destination.to_string(),
+                UpdateEvent::Menu(menu),
+            ))
+            .await?;
         }
 
-        tx.send(Event::Update(
-            destination.to_string(),
-            UpdateEvent::Menu(menu),
-        ))?;
-
         let mut layout_updated = dbus_menu_proxy.receive_layout_updated().await?;
         let mut properties_updated = dbus_menu_proxy.receive_items_properties_updated().await?;
+        let mut status_changed = dbus_menu_proxy.receive_status_changed().await;
+        let mut text_direction_changed = dbus_menu_proxy.receive_text_direction_changed().await;
 
         loop {
             tokio::select!(
-                Some(_) = layout_updated.next() => {
+                Some(mut signal) = layout_updated.next() => {
                     debug!("[{destination}{menu_path}] layout update");
 
-                    let get_layout = dbus_menu_proxy.get_layout(0, 10, &[]);
+                    if debug {
+                        log_raw_signal(&destination, signal.message());
+                    }
+
+                    // applications often send several updates for one change,
+                    // so wait briefly and only fetch the layout once for all of them
+                    ctx.config.clock.sleep(LAYOUT_COALESCE_WINDOW).await;
+                    let mut signals = 1;
+                    while signals < MAX_SIGNAL_BATCH {
+                        let Some(Some(next)) = poll_once(layout_updated.next()).await else {
+                            break;
+                        };
+                        if debug {
+                            log_raw_signal(&destination, next.message());
+                        }
+                        signal = next;
+                        signals += 1;
+                    }
+
+                    let started = Instant::now();
+
+                    let signal_revision = signal.args().map(|args| args.revision).ok();
+                    if let (Some(signal_revision), Some(revision)) = (signal_revision, revision) {
+                        if signal_revision < revision {
+                            debug!(
+                                "[{destination}{menu_path}] skipping stale layout update \
+                                 for revision {signal_revision}"
+                            );
+                            ctx.record_signals(&destination, signals, started);
+                            continue;
+                        }
+                    }
+
+                    let get_layout = with_retries(&ctx.config, "GetLayout", || {
+                        fetch_menu(&dbus_menu_proxy, &destination, &ctx)
+                    });
 
-                    let menu = match timeout(Duration::from_secs(1), get_layout).await {
-                        Ok(Ok(menu)) => {
+                    let menu = match get_layout.await {
+                        Ok(menu) => {
                             debug!("got new menu layout");
                             menu
                         }
-                        Ok(Err(err)) => {
+                        Err(err) => {
                             error!("error fetching layout: {err:?}");
-                            break;
-                        }
-                        Err(_) => {
-                            error!("Timeout getting layout");
-                            break;
+                            ctx.send(Event::Warning(
+                                destination.to_string(),
+                                Warning::CallFailed {
+                                    method: "GetLayout".to_string(),
+                                    message: err.to_string(),
+                                },
+                            ))
+                            .await?;
+                            continue;
                         }
                     };
 
-                    let menu = TrayMenu::try_from(menu)?;
-
-                    if let Some((_, menu_cache)) = items
-                        .lock()
-                        .expect("mutex lock should succeed")
-                        .get_mut(&destination)
-                    {
-                        menu_cache.replace(menu.clone());
-                    } else {
-                        error!("could not find item in state");
+                    if debug {
+                        debug!(target: RAW_TARGET, "[{destination}{menu_path}] GetLayout: {menu:?}");
                     }
 
                     debug!("sending new menu for '{destination}'");
                     trace!("new menu for '{destination}': {menu:?}");
-                    tx.send(Event::Update(
+                    revision = Some(menu.id);
+                    ctx.send(Event::Update(
                         destination.to_string(),
                         UpdateEvent::Menu(menu),
-                    ))?;
+                    ))
+                    .await?;
+                    ctx.record_signals(&destination, signals, started);
                 }
                 Some(change) = properties_updated.next() => {
+                    if debug {
+                        log_raw_signal(&destination, change.message());
+                    }
+
+                    let started = Instant::now();
+
                     let body = change.message().body();
                     let update: PropertiesUpdate= body.deserialize::<PropertiesUpdate>()?;
-                    let diffs = Vec::try_from(update)?;
 
-                    tx.send(Event::Update(
+                    // an update which cannot be applied to the cached layout
+                    // is dropped in favour of fetching the whole layout again
+                    let diffs = match Vec::<MenuDiff>::try_from(update) {
+                        Ok(diffs) => Some(diffs),
+                        Err(err) => {
+                            warn!("[{destination}{menu_path}] invalid property update: {err:?}");
+                            None
+                        }
+                    };
+
+                    let missed_update = lock(&ctx.items)
+                        .get(&destination)
+                        .and_then(|(_, menu)| menu.as_ref())
+                        .is_some_and(|menu| {
+                            diffs.iter().flatten().any(|diff| {
+                                diff.id != 0 && menu.find_item(diff.id).is_none()
+                            })
+                        });
+
+                    let Some(diffs) = diffs.filter(|_| !missed_update) else {
+                        debug!(
+                            "[{destination}{menu_path}] cannot apply property update \
+                             to the cached layout, fetching it again"
+                        );
+
+                        match fetch_menu(&dbus_menu_proxy, &destination, &ctx).await {
+                            Ok(menu) => {
+                                revision = Some(menu.id);
+                                ctx.send(Event::Update(
+                                    destination.to_string(),
+                                    UpdateEvent::Menu(menu),
+                                ))
+                                .await?;
+                            }
+                            Err(err) => error!("error fetching layout: {err:?}"),
+                        }
+
+                        ctx.record_signals(&destination, 1, started);
+                        continue;
+                    };
+
+                    ctx.send(Event::Update(
                         destination.to_string(),
                         UpdateEvent::MenuDiff(diffs),
-                    ))?;
-
-                    // FIXME: Menu cache gonna be out of sync
+                    ))
+                    .await?;
+                    ctx.record_signals(&destination, 1, started);
+                }
+                Some(change) = status_changed.next() => {
+                    if let Ok(status) = change.get().await {
+                        ctx.send(Event::Update(
+                            destination.to_string(),
+                            UpdateEvent::MenuStatus(MenuStatus::from(status.as_str())),
+                        ))
+                        .await?;
+                    }
+                }
+                Some(change) = text_direction_changed.next() => {
+                    if let Ok(text_direction) = change.get().await {
+                        ctx.send(Event::Update(
+                            destination.to_string(),
+                            UpdateEvent::MenuTextDirection(TextDirection::from(
+                                text_direction.as_str(),
+                            )),
+                        ))
+                        .await?;
+                    }
                 }
+                else => break,
             );
         }
 
@@ -560,12 +3375,25 @@ impl Client {
     async fn get_notifier_item_proxy(
         &self,
         address: String,
-    ) -> crate::error::Result<StatusNotifierItemProxy<'_>> {
-        let proxy = StatusNotifierItemProxy::builder(&self.connection)
-            .destination(address)?
-            .path(ITEM_OBJECT)?
+    ) -> crate::error::Result<StatusNotifierItemProxy<'static>> {
+        let ctx = self.ctx();
+        let path = ctx.item_path(&address);
+        if let Some(proxy) = lock(&ctx.proxies)
+            .get(&address)
+            .and_then(|proxies| proxies.item.clone())
+            .filter(|proxy| proxy.inner().path().as_str() == path)
+        {
+            return Ok(proxy);
+        }
+
+        let proxy = StatusNotifierItemProxy::builder(&ctx.connection)
+            .destination(ctx.owner(&address))?
+            .path(path)?
+            .cache_properties(CacheProperties::No)
             .build()
             .await?;
+
+        lock(&ctx.proxies).entry(address).or_default().item = Some(proxy.clone());
         Ok(proxy)
     }
 
@@ -573,42 +3401,538 @@ impl Client {
         &self,
         address: String,
         menu_path: String,
-    ) -> crate::error::Result<DBusMenuProxy<'_>> {
-        let proxy = DBusMenuProxy::builder(&self.connection)
-            .destination(address)?
-            .path(menu_path)?
+    ) -> crate::error::Result<DBusMenuProxy<'static>> {
+        let ctx = self.ctx();
+        let (proxy, created) = ctx.menu_proxy(&address, &menu_path).await?;
+
+        // menus are only watched once first used, unless they were prefetched
+        if created && ctx.config.track_menus {
+            let debug = ctx
+                .config
+                .debug_destination
+                .as_deref()
+                .is_some_and(|name| name == address || name == ctx.owner(&address));
+
+            let task_ctx = ctx.clone();
+            let task_address = address.clone();
+            let task_proxy = proxy.clone();
+            ctx.spawn_for_menu(&address, async move {
+                Self::watch_menu(task_address, task_proxy, false, debug, task_ctx).await
+            });
+        }
+
+        Ok(proxy)
+    }
+
+    /// Subscribes to the events broadcast channel,
+    /// returning a new receiver.
+    ///
+    /// Once the client is dropped, the receiver will close.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.ctx().tx.subscribe()
+    }
+
+    /// Subscribes to events through a channel holding up to `capacity` events,
+    /// which the client waits on when full rather than dropping events.
+    ///
+    /// This suits consumers which must see every change,
+    /// but a receiver which stops receiving without being dropped stalls the client.
+    /// The capacity is at least 1.
+    ///
+    /// Once the client is dropped, the receiver will close.
+    #[must_use]
+    pub fn subscribe_bounded(&self, capacity: usize) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        lock(&self.ctx().subscribers).push(tx);
+        rx
+    }
+
+    /// Subscribes to events,
+    /// returning a receiver which handles missed events according to the [`LagPolicy`].
+    ///
+    /// Once the client is dropped, the receiver will close.
+    #[must_use]
+    pub fn events(&self) -> EventReceiver {
+        let ctx = self.ctx();
+
+        EventReceiver {
+            rx: ctx.tx.subscribe(),
+            items: ctx.items.clone(),
+            lag_policy: ctx.config.lag_policy,
+        }
+    }
+
+    /// Gets the number of receivers currently subscribed to events,
+    /// from both [`Client::subscribe`] and [`Client::subscribe_bounded`],
+    /// not counting the one held internally by the client.
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        let ctx = self.ctx();
+        let bounded = lock(&ctx.subscribers)
+            .iter()
+            .filter(|subscriber| !subscriber.is_closed())
+            .count();
+
+        ctx.tx.receiver_count().saturating_sub(1) + bounded
+    }
+
+    /// Gets all current items, including their menus if present and loaded.
+    #[must_use]
+    pub fn items(&self) -> Arc<Mutex<State>> {
+        self.ctx().items.clone()
+    }
+
+    /// Gets the number of current items.
+    ///
+    /// This only holds the state lock long enough to read the length.
+    #[must_use]
+    pub fn items_len(&self) -> usize {
+        lock(&self.ctx().items).len()
+    }
+
+    /// Gets whether there are no current items.
+    #[must_use]
+    pub fn items_is_empty(&self) -> bool {
+        self.items_len() == 0
+    }
+
+    /// Gets what the item at `address` was found to implement,
+    /// if items are introspected, see [`ClientBuilder::introspect_items`].
+    #[must_use]
+    pub fn capabilities(&self, address: &str) -> Option<Capabilities> {
+        lock(&self.ctx().capabilities).get(address).cloned()
+    }
+
+    /// Gets a snapshot of what the client is doing,
+    /// including how many signals each item has sent.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        let ctx = self.ctx();
+
+        let tasks = {
+            let tasks = lock(&ctx.tasks);
+            tasks
+                .client
+                .iter()
+                .chain(tasks.items.values().flatten())
+                .chain(tasks.menus.values())
+                .filter(|task| !task.is_finished())
+                .count()
+        };
+
+        let items = lock(&ctx.items).len();
+        let pending_items = lock(&ctx.pending).len();
+        let item_stats = lock(&ctx.stats).clone();
+
+        Stats {
+            items,
+            pending_items,
+            tasks,
+            item_stats,
+        }
+    }
+
+    /// Gets the main icon of the item at `address`,
+    /// first loading the pixmaps of any of its icons which are not set.
+    ///
+    /// This is only needed if pixmaps are loaded lazily,
+    /// otherwise it is the same as calling [`StatusNotifierItem::icon`].
+    /// See [`ClientBuilder::lazy_pixmaps`].
+    ///
+    /// A pixmap update event is sent for each pixmap loaded.
+    /// Each pixmap is only fetched once, until the item signals that it changed,
+    /// although one which timed out is fetched again on the next call.
+    /// Invalid pixmaps are skipped with a [`Warning::InvalidProperty`].
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if no item is registered at `address`,
+    /// or if the connection to the `DBus` object fails.
+    pub async fn load_icon(&self, address: &str) -> crate::error::Result<Icon> {
+        let ctx = self.ctx();
+
+        let item = lock(&ctx.items)
+            .get(address)
+            .map(|(item, _)| item.clone())
+            .ok_or_else(|| Error::UnknownItem(address.to_string()))?;
+
+        if !ctx.config.lazy_pixmaps {
+            return Ok(item.icon());
+        }
+
+        let slots: [(_, _, fn(_) -> UpdateEvent); 3] = [
+            (item.icon(), "IconPixmap", UpdateEvent::IconPixmap),
+            (
+                item.overlay_icon(),
+                "OverlayIconPixmap",
+                UpdateEvent::OverlayIconPixmap,
+            ),
+            (
+                item.attention_icon(),
+                "AttentionIconPixmap",
+                UpdateEvent::AttentionIconPixmap,
+            ),
+        ];
+
+        // slots fetched before are skipped, even if the item did not provide them
+        let missing = {
+            let loaded_pixmaps = lock(&ctx.loaded_pixmaps);
+            let loaded = loaded_pixmaps.get(address);
+
+            slots
+                .iter()
+                .filter(|(icon, name, _)| {
+                    matches!(icon, Icon::None) && loaded.is_none_or(|loaded| !loaded.contains(name))
+                })
+                .map(|(_, name, _)| *name)
+                .collect::<Vec<_>>()
+        };
+
+        if missing.is_empty() {
+            return Ok(item.icon());
+        }
+
+        let path = ctx.item_path(address);
+
+        let properties_proxy = PropertiesProxy::builder(&ctx.connection)
+            .destination(ctx.owner(address))?
+            .path(path)?
             .build()
             .await?;
-        Ok(proxy)
+
+        let interface = InterfaceName::from_static_str(PROPERTIES_INTERFACE)
+            .expect("to be valid interface name");
+        let mark_loaded = |name| {
+            lock(&ctx.loaded_pixmaps)
+                .entry(address.to_string())
+                .or_default()
+                .insert(name);
+        };
+
+        let swap_byte_order = ctx.config.swap_pixmap_byte_order(&item.id);
+        for (_, name, event) in slots {
+            if !missing.contains(&name) {
+                continue;
+            }
+
+            let get = properties_proxy.get(interface.clone(), name);
+            let value = match ctx.call(address, &format!("Get {name}"), get).await {
+                Ok(value) => value,
+                // items reply with an error for pixmaps they do not have,
+                // which is not worth asking again
+                Err(err) if !is_transient(&err) => {
+                    debug!("[{address}] skipping pixmap {name}: {err}");
+                    mark_loaded(name);
+                    continue;
+                }
+                Err(err) => {
+                    debug!("[{address}] failed to load pixmap {name}: {err}");
+                    continue;
+                }
+            };
+            mark_loaded(name);
+
+            let properties = DBusProps(HashMap::from([(name.to_string(), value)]));
+            match properties.get_icon_pixmap(name) {
+                Some(Ok(mut pixmaps)) => {
+                    IconPixmap::fix_byte_order(&mut pixmaps, swap_byte_order);
+                    ctx.send(Event::Update(address.to_string(), event(Some(pixmaps))))
+                        .await?;
+                }
+                Some(Err(err)) => {
+                    warn!("[{address}] skipping invalid property {name}: {err}");
+
+                    ctx.send(Event::Warning(
+                        address.to_string(),
+                        Warning::InvalidProperty {
+                            name: name.to_string(),
+                            message: err.to_string(),
+                        },
+                    ))
+                    .await?;
+                }
+                None => {}
+            }
+        }
+
+        let icon = lock(&ctx.items)
+            .get(address)
+            .map_or(Icon::None, |(item, _)| item.icon());
+
+        Ok(icon)
+    }
+
+    /// Gets a copy of the cached menu for the item at `address`,
+    /// if the item exists and its menu has been loaded.
+    ///
+    /// See [`Client::with_menu_layout`] to avoid copying the menu.
+    #[must_use]
+    pub fn menu_layout(&self, address: &str) -> Option<TrayMenu> {
+        self.with_menu_layout(address, |menu| menu.cloned())
+    }
+
+    /// Calls `f` with a reference to the cached menu for the item at `address`,
+    /// if the item exists and its menu has been loaded,
+    /// returning its result.
+    ///
+    /// The state lock is held while `f` runs.
+    /// It must not block, or call back into the client.
+    pub fn with_menu_layout<R>(&self, address: &str, f: impl FnOnce(Option<&TrayMenu>) -> R) -> R {
+        let ctx = self.ctx();
+        let items = lock(&ctx.items);
+        f(items.get(address).and_then(|(_, menu)| menu.as_ref()))
+    }
+
+    /// Gets a stream yielding each currently known item once,
+    /// along with its address, then completing.
+    ///
+    /// The stream reads from a snapshot of the current state,
+    /// so it completes promptly.
+    /// Items added after the call are only reported through [`Client::subscribe`].
+    pub fn initial_items(&self) -> impl Stream<Item = (String, StatusNotifierItem)> {
+        futures_lite::stream::iter(snapshot(&self.ctx().items))
+    }
+
+    /// One should call this method with id=0 when opening the root menu.
+    ///
+    /// ID refers to the menuitem id.
+    /// Returns `needsUpdate`
+    ///
+    /// Calls for the same menu item are limited,
+    /// see [`ClientBuilder::about_to_show_cooldown`].
+    pub async fn about_to_show_menuitem(
+        &self,
+        address: String,
+        menu_path: String,
+        id: i32,
+    ) -> crate::error::Result<bool> {
+        let proxy = self.get_menu_proxy(address.clone(), menu_path).await?;
+        self.ctx().about_to_show(&proxy, &address, id).await
+    }
+
+    /// Gets the protocol version of the menu of the item at `address`,
+    /// from the cached menu if it has been loaded, otherwise from the menu itself.
+    async fn menu_version(proxy: &DBusMenuProxy<'_>, address: &str, ctx: &Context) -> Option<u32> {
+        let cached = lock(&ctx.items)
+            .get(address)
+            .and_then(|(_, menu)| menu.as_ref().map(|menu| menu.version));
+
+        match cached {
+            Some(version) => version,
+            None => ctx.call(address, "Get Version", proxy.version()).await.ok(),
+        }
+    }
+
+    /// Batched version of [`Client::about_to_show_menuitem`],
+    /// to be called with the ids of all menu items about to be shown at once.
+    ///
+    /// Returns the ids of the menu items which need to be updated.
+    /// Ids the application did not recognise are logged and skipped.
+    ///
+    /// Menus reporting a protocol version older than the batched call,
+    /// or which do not implement it, are sent `AboutToShow` separately for each id instead.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if the connection to the `DBus` object fails.
+    pub async fn about_to_show_menuitem_group(
+        &self,
+        address: String,
+        menu_path: String,
+        ids: &[i32],
+    ) -> crate::error::Result<Vec<i32>> {
+        let ctx = self.ctx();
+        let proxy = self.get_menu_proxy(address.clone(), menu_path).await?;
+
+        let supports_group = !ctx.lacks_menu_method(&address, "AboutToShowGroup")
+            && Self::menu_version(&proxy, &address, &ctx)
+                .await
+                .is_none_or(|version| version >= MENU_GROUP_VERSION);
+
+        if supports_group {
+            let about_to_show_group = proxy.about_to_show_group(ids);
+            match ctx
+                .call(&address, "AboutToShowGroup", about_to_show_group)
+                .await
+            {
+                Ok((updates_needed, id_errors)) => {
+                    if !id_errors.is_empty() {
+                        warn!("aboutToShowGroup did not recognise ids: {id_errors:?}");
+                    }
+
+                    return Ok(updates_needed);
+                }
+                Err(Error::ZBus(err)) if is_unknown_method(&err) => {
+                    debug!("menu does not implement aboutToShowGroup: {err}");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut updates_needed = vec![];
+        for &id in ids {
+            match ctx
+                .call(&address, "AboutToShow", proxy.about_to_show(id))
+                .await
+            {
+                Ok(true) => updates_needed.push(id),
+                Ok(false) => {}
+                Err(Error::ZBus(err @ zbus::Error::MethodError(..))) => {
+                    warn!("aboutToShow did not recognise id {id}: {err}");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(updates_needed)
+    }
+
+    /// Fetches the current properties of the menu items `ids` of the item at `address`,
+    /// using a single `GetGroupProperties` call.
+    ///
+    /// The items are replaced in the cached menu, keeping their submenus,
+    /// and the updated menu is sent as an [`UpdateEvent::Menu`] and returned.
+    /// This is cheaper than fetching the whole layout
+    /// when only the properties of some items may be out of date.
+    ///
+    /// Returns `None` if the menu has not been loaded.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if no item is registered at `address`,
+    /// if the item does not have a menu,
+    /// or if the call to the `DBus` object fails.
+    pub async fn refresh_menu_items(
+        &self,
+        address: &str,
+        ids: &[i32],
+    ) -> crate::error::Result<Option<TrayMenu>> {
+        let menu_path = self.menu_path(address)?;
+        let proxy = self.get_menu_proxy(address.to_string(), menu_path).await?;
+
+        self.ctx().refresh_menu_items(&proxy, address, ids).await
+    }
+
+    /// Prepares the root menu of the item at `address` for display,
+    /// returning its up-to-date layout.
+    ///
+    /// This performs the full open sequence expected by applications, in order:
+    ///
+    /// 1. `AboutToShow` is called for the root menu, and awaited,
+    ///    giving applications with dynamic menus a chance to rebuild them.
+    /// 2. The layout is fetched with `GetLayout`.
+    ///    The cached menu is replaced and an [`UpdateEvent::Menu`] is sent.
+    /// 3. The `opened` event is sent for the root menu.
+    ///
+    /// Each call should be paired with a call to [`Client::close_menu`]
+    /// once the menu is hidden again.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if no item is registered at `address`,
+    /// if the item does not have a menu,
+    /// or if any of the calls to the `DBus` object fail.
+    pub async fn open_menu(&self, address: &str) -> crate::error::Result<TrayMenu> {
+        let menu_path = self.menu_path(address)?;
+        let proxy = self.get_menu_proxy(address.to_string(), menu_path).await?;
+
+        let ctx = self.ctx();
+        ctx.about_to_show(&proxy, address, 0).await?;
+
+        let menu = fetch_menu(&proxy, address, &ctx).await?;
+        ctx.send(Event::Update(
+            address.to_string(),
+            UpdateEvent::Menu(menu.clone()),
+        ))
+        .await?;
+
+        send_menu_event(&proxy, 0, "opened").await?;
+
+        ctx.prefetch_submenus(proxy, address, &menu.submenus);
+
+        Ok(menu)
+    }
+
+    /// Prepares the submenu of the menu item `id` for display,
+    /// fetching its children if they were beyond the depth of the cached layout.
+    ///
+    /// `AboutToShow` is called for the item,
+    /// then its children are fetched to the configured depth and merged into the cached menu.
+    /// The updated menu is sent as an [`UpdateEvent::Menu`] and returned.
+    ///
+    /// See [`ClientBuilder::menu_layout_depth`].
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if no item is registered at `address`,
+    /// if the item does not have a menu,
+    /// if the menu has no item `id`,
+    /// or if any of the calls to the `DBus` object fail.
+    pub async fn open_submenu(&self, address: &str, id: i32) -> crate::error::Result<TrayMenu> {
+        let menu_path = self.menu_path(address)?;
+        let proxy = self.get_menu_proxy(address.to_string(), menu_path).await?;
+
+        let ctx = self.ctx();
+        let menu = ctx.load_submenu(&proxy, address, id).await?;
+
+        send_menu_event(&proxy, id, "opened").await?;
+
+        if let Some(item) = menu.find_item(id) {
+            ctx.prefetch_submenus(proxy, address, &item.submenu);
+        }
+
+        Ok(menu)
     }
 
-    /// Subscribes to the events broadcast channel,
-    /// returning a new receiver.
+    /// Notifies the application that the root menu of the item at `address` was hidden,
+    /// by sending the `closed` event.
     ///
-    /// Once the client is dropped, the receiver will close.
-    #[must_use]
-    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
-        self.tx.subscribe()
+    /// Once the application has received the event,
+    /// an [`UpdateEvent::MenuClosed`] is sent to subscribers.
+    ///
+    /// This should be called once for each call to [`Client::open_menu`].
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if no item is registered at `address`,
+    /// if the item does not have a menu,
+    /// or if sending the event fails.
+    pub async fn close_menu(&self, address: &str) -> crate::error::Result<()> {
+        let menu_path = self.menu_path(address)?;
+        let proxy = self.get_menu_proxy(address.to_string(), menu_path).await?;
+
+        send_menu_event(&proxy, 0, "closed").await?;
+
+        self.ctx()
+            .send(Event::Update(address.to_string(), UpdateEvent::MenuClosed))
+            .await?;
+
+        Ok(())
     }
 
-    /// Gets all current items, including their menus if present.
-    #[must_use]
-    pub fn items(&self) -> Arc<Mutex<State>> {
-        self.items.clone()
+    /// Gets the path of the menu object for the item at `address`.
+    fn menu_path(&self, address: &str) -> crate::error::Result<String> {
+        let ctx = self.ctx();
+        let items = lock(&ctx.items);
+        let (item, _) = items
+            .get(address)
+            .ok_or_else(|| Error::UnknownItem(address.to_string()))?;
+
+        item.menu.clone().ok_or(Error::MissingProperty("Menu"))
     }
 
-    /// One should call this method with id=0 when opening the root menu.
-    ///
-    /// ID refers to the menuitem id.
-    /// Returns `needsUpdate`
-    pub async fn about_to_show_menuitem(
+    /// Sends the event `event_id`, such as `hovered`, to the menu item `id`
+    /// of the item at `address`.
+    pub(crate) async fn send_menu_item_event(
         &self,
-        address: String,
-        menu_path: String,
+        address: &str,
         id: i32,
-    ) -> crate::error::Result<bool> {
-        let proxy = self.get_menu_proxy(address, menu_path).await?;
-        Ok(proxy.about_to_show(id).await?)
+        event_id: &str,
+    ) -> crate::error::Result<()> {
+        let menu_path = self.menu_path(address)?;
+        let proxy = self.get_menu_proxy(address.to_string(), menu_path).await?;
+        send_menu_event(&proxy, id, event_id).await
     }
 
     /// Sends an activate request for a menu item.
@@ -622,9 +3946,13 @@ impl Client {
     ///
     /// If the system time is somehow before the Unix epoch.
     pub async fn activate(&self, req: ActivateRequest) -> crate::error::Result<()> {
+        let clock = self.ctx().config.clock.clone();
         macro_rules! timeout_event {
             ($event:expr) => {
-                if timeout(Duration::from_secs(1), $event).await.is_err() {
+                if clock::timeout(&*clock, Duration::from_secs(1), $event)
+                    .await
+                    .is_none()
+                {
                     error!("Timed out sending activate event");
                 }
             };
@@ -636,16 +3964,7 @@ impl Client {
                 submenu_id,
             } => {
                 let proxy = self.get_menu_proxy(address, menu_path).await?;
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("time should flow forwards");
-
-                let event = proxy.event(
-                    submenu_id,
-                    "clicked",
-                    &Value::I32(0),
-                    timestamp.as_secs() as u32,
-                );
+                let event = send_menu_event(&proxy, submenu_id, "clicked");
 
                 timeout_event!(event);
             }
@@ -665,24 +3984,565 @@ impl Client {
 
         Ok(())
     }
+
+    /// Activates the item at `address`, whose icon is shown at `rect`.
+    ///
+    /// The position hint sent to the item is the [`Rect::anchor`] point,
+    /// so that any window it opens is placed next to the icon.
+    /// Use [`Client::activate`] to send an exact position.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if the connection to the `DBus` object fails.
+    pub async fn activate_at(&self, address: String, rect: Rect) -> crate::error::Result<()> {
+        let (x, y) = rect.anchor();
+        self.activate(ActivateRequest::Default { address, x, y })
+            .await
+    }
+
+    /// Sends an activate request like [`Client::activate`],
+    /// first passing an XDG activation token to the item
+    /// so that it can transfer focus to its window under Wayland.
+    ///
+    /// Items which do not implement `ProvideXdgActivationToken`
+    /// are activated as normal.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if the connection to the `DBus` object fails,
+    /// or if sending the event fails for any reason.
+    pub async fn activate_with_token(
+        &self,
+        req: ActivateRequest,
+        token: &str,
+    ) -> crate::error::Result<()> {
+        let address = match &req {
+            ActivateRequest::MenuItem { address, .. }
+            | ActivateRequest::Default { address, .. }
+            | ActivateRequest::Secondary { address, .. } => address.clone(),
+        };
+
+        let ctx = self.ctx();
+        if !ctx.lacks_method(&address, "ProvideXdgActivationToken") {
+            let proxy = self.get_notifier_item_proxy(address.clone()).await?;
+            let provide_token = proxy.provide_xdg_activation_token(token);
+            if let Err(err) = ctx
+                .call(&address, "ProvideXdgActivationToken", provide_token)
+                .await
+            {
+                debug!("{address} did not accept activation token: {err}");
+            }
+        }
+
+        self.activate(req).await
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.reconnect_task.abort();
+        lock(&self.ctx).abort_tasks();
+    }
+}
+
+/// Gets each of the named item properties in turn,
+/// skipping any which cannot be fetched.
+///
+/// The rest are skipped once one times out.
+async fn get_properties(
+    properties_proxy: &PropertiesProxy<'_>,
+    names: &[&str],
+    ctx: &Context,
+) -> HashMap<String, OwnedValue> {
+    let interface =
+        InterfaceName::from_static_str(PROPERTIES_INTERFACE).expect("to be valid interface name");
+    let destination = &ctx.address(properties_proxy.inner().destination());
+
+    let mut properties = HashMap::with_capacity(names.len());
+    for &name in names {
+        let get = properties_proxy.get(interface.clone(), name);
+        match ctx.call(destination, &format!("Get {name}"), get).await {
+            Ok(value) => {
+                properties.insert(name.to_string(), value);
+            }
+            Err(Error::ZBusFdo(zbus::fdo::Error::Timeout(_))) => {
+                warn!(
+                    "[{destination}{}] Get {name} timed out, skipping remaining properties",
+                    properties_proxy.inner().path()
+                );
+                break;
+            }
+            Err(err) => debug!("skipping property {name}: {err}"),
+        }
+    }
+
+    properties
+}
+
+/// Creates the timer for checking the item at `destination` is responsive every `period`.
+///
+/// The first check is offset by an amount derived from the address,
+/// so that checks for items registered together are spread out.
+fn liveness_timer(destination: &str, period: Duration, clock: &Arc<dyn Clock>) -> Ticker {
+    let mut hasher = DefaultHasher::new();
+    destination.hash(&mut hasher);
+    let offset = period.mul_f64((hasher.finish() % 1000) as f64 / 1000.0);
+
+    Ticker::new(clock.clone(), clock.now() + period + offset, period)
+}
+
+/// Gets the methods of each interface in the introspection data `xml`,
+/// keyed on the interface name.
+///
+/// Only `interface` and `method` elements are read, which is all probing needs,
+/// so this is not a full XML parser.
+fn parse_interfaces(xml: &str) -> HashMap<String, InterfaceMembers> {
+    let mut interfaces = HashMap::new();
+    let mut current: Option<&mut InterfaceMembers> = None;
+
+    for tag in xml.split('<').skip(1) {
+        if let Some(attributes) = element_attributes(tag, "interface") {
+            current =
+                xml_name(attributes).map(|name| interfaces.entry(name.to_string()).or_default());
+        } else if tag.starts_with("/interface") {
+            current = None;
+        } else if let Some(members) = current.as_mut() {
+            let (members, attributes) = match element_attributes(tag, "method") {
+                Some(attributes) => (&mut members.methods, attributes),
+                None => match element_attributes(tag, "signal") {
+                    Some(attributes) => (&mut members.signals, attributes),
+                    None => continue,
+                },
+            };
+
+            if let Some(name) = xml_name(attributes) {
+                members.push(name.to_string());
+            }
+        }
+    }
+
+    interfaces
+}
+
+/// The members of an interface found by introspection.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct InterfaceMembers {
+    methods: Vec<String>,
+    signals: Vec<String>,
+}
+
+/// Gets the attributes of `tag` if it opens an `element`.
+fn element_attributes<'a>(tag: &'a str, element: &str) -> Option<&'a str> {
+    tag.strip_prefix(element)
+        .filter(|attributes| attributes.starts_with(char::is_whitespace))
+}
+
+/// Gets the value of the `name` attribute from `attributes`.
+fn xml_name(attributes: &str) -> Option<&str> {
+    let (_, value) = attributes.split_once("name=")?;
+    let quote = value.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    value[1..].split(quote).next()
+}
+
+/// Whether `update` carries icon pixmaps.
+fn is_pixmap_update(update: &UpdateEvent) -> bool {
+    matches!(
+        update,
+        UpdateEvent::IconPixmap(_)
+            | UpdateEvent::OverlayIconPixmap(_)
+            | UpdateEvent::AttentionIconPixmap(_)
+    )
+}
+
+/// Waits for the next tick of `timer`, or forever if there is no timer.
+async fn next_tick(timer: &mut Option<Ticker>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits for the next item from `stream`, or forever if there is none.
+async fn next_in<S: Stream + Unpin>(stream: &mut Option<S>) -> Option<S::Item> {
+    match stream {
+        Some(stream) => stream.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Whether a call failed because the application does not implement the method.
+fn is_unknown_method(err: &zbus::Error) -> bool {
+    match err {
+        zbus::Error::MethodError(name, _, _) => {
+            name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod"
+        }
+        zbus::Error::FDO(err) => matches!(**err, zbus::fdo::Error::UnknownMethod(_)),
+        _ => false,
+    }
+}
+
+/// Calls `call` until it succeeds, fails with an error which is unlikely to pass,
+/// or has been made as many times as the retry policy in `config` allows.
+async fn with_retries<T, F, Fut>(config: &Config, method: &str, mut call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Err(err) if attempt < config.retry_policy.max_attempts && is_transient(&err) => {
+                let delay = config.retry_policy.delay(attempt);
+                debug!("{method} failed, retrying in {delay:?}: {err}");
+
+                config.clock.sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether a failed call is likely to succeed if made again,
+/// because the application did not answer or is still setting up its objects.
+fn is_transient(err: &Error) -> bool {
+    use zbus::fdo::Error::*;
+
+    let fdo = match err {
+        Error::ZBusFdo(err) => err,
+        Error::ZBus(zbus::Error::FDO(err)) => err,
+        Error::ZBus(zbus::Error::MethodError(name, _, _)) => {
+            return matches!(
+                name.as_str(),
+                "org.freedesktop.DBus.Error.NoReply"
+                    | "org.freedesktop.DBus.Error.Timeout"
+                    | "org.freedesktop.DBus.Error.TimedOut"
+                    | "org.freedesktop.DBus.Error.LimitsExceeded"
+                    | "org.freedesktop.DBus.Error.UnknownObject"
+            );
+        }
+        _ => return false,
+    };
+
+    matches!(
+        fdo,
+        NoReply(_) | Timeout(_) | TimedOut(_) | LimitsExceeded(_) | UnknownObject(_)
+    )
+}
+
+/// Awaits `call` to `method`, failing with a timeout error once `limit` has passed on `clock`.
+async fn with_timeout<T, E>(
+    clock: &dyn Clock,
+    limit: Duration,
+    method: &str,
+    call: impl Future<Output = std::result::Result<T, E>>,
+) -> Result<T>
+where
+    Error: From<E>,
+{
+    match clock::timeout(clock, limit, call).await {
+        Some(result) => Ok(result?),
+        None => Err(timeout_error(method)),
+    }
+}
+
+/// Creates the error for a call to `method` which was given up on.
+fn timeout_error(method: &str) -> Error {
+    Error::ZBusFdo(zbus::fdo::Error::Timeout(format!("{method} timed out")))
+}
+
+/// Waits until `connection` fails, for example because the bus daemon exited.
+///
+/// # Errors
+///
+/// Returns an error if the connection cannot be watched,
+/// in which case it has not necessarily failed.
+async fn wait_for_disconnect(connection: &Connection) -> Result<()> {
+    // the rule matches a signal which is rarely sent,
+    // but every stream is sent an error once the connection fails
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface("org.freedesktop.DBus")?
+        .member("NameLost")?
+        .build();
+
+    let mut stream = MessageStream::for_match_rule(rule, connection, Some(1)).await?;
+    while let Some(Ok(_)) = stream.next().await {}
+
+    Ok(())
+}
+
+/// Whether a call to the properties interface failed
+/// because the item does not implement the interface at all.
+fn lacks_properties_interface(err: &zbus::fdo::Error) -> bool {
+    use zbus::fdo::Error::*;
+    matches!(err, UnknownInterface(_) | UnknownMethod(_))
+}
+
+/// Whether an error replying to a ping means the item has left the bus.
+fn is_disconnected(err: &zbus::Error) -> bool {
+    use zbus::fdo::Error::*;
+
+    match err {
+        zbus::Error::MethodError(name, _, _) => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner"
+        ),
+        zbus::Error::FDO(err) => matches!(**err, ServiceUnknown(_) | NameHasNoOwner(_)),
+        _ => false,
+    }
+}
+
+/// Whether an error replying to a liveness check means the item is unresponsive,
+/// as opposed to it replying with an error of its own.
+fn is_unresponsive(err: &zbus::fdo::Error) -> bool {
+    use zbus::fdo::Error::*;
+    matches!(
+        err,
+        NoReply(_) | Timeout(_) | TimedOut(_) | ServiceUnknown(_) | NameHasNoOwner(_)
+    )
+}
+
+/// Copies each item in the state along with its address.
+fn snapshot(items: &Mutex<State>) -> Vec<(String, StatusNotifierItem)> {
+    lock(items)
+        .iter()
+        .map(|(address, (item, _))| (address.clone(), item.clone()))
+        .collect()
+}
+
+/// Fetches the full menu layout from a `DBusMenu` object,
+/// along with the protocol version it implements and its icon theme path.
+///
+/// Each call is subject to the call timeout of the item at `address`.
+async fn fetch_menu(proxy: &DBusMenuProxy<'_>, address: &str, ctx: &Context) -> Result<TrayMenu> {
+    let config = &ctx.config;
+    let menu_properties = config.menu_properties();
+    let get_layout = proxy.get_layout(0, config.menu_layout_depth, &menu_properties);
+    let layout = ctx.call(address, "GetLayout", get_layout).await?;
+    let mut menu = TrayMenu::from_layout(layout, config.max_menu_depth)?;
+    menu.sanitize(config.max_text_length);
+
+    menu.version = match ctx.call(address, "Get Version", proxy.version()).await {
+        Ok(version) => Some(version),
+        Err(err) => {
+            debug!("menu did not report a version: {err}");
+            None
+        }
+    };
+
+    menu.icon_theme_path = ctx
+        .call(address, "Get IconThemePath", proxy.icon_theme_path())
+        .await
+        .unwrap_or_default();
+
+    if let Ok(status) = ctx.call(address, "Get Status", proxy.status()).await {
+        menu.status = MenuStatus::from(status.as_str());
+    }
+
+    if let Ok(text_direction) = ctx
+        .call(address, "Get TextDirection", proxy.text_direction())
+        .await
+    {
+        menu.text_direction = TextDirection::from(text_direction.as_str());
+    }
+
+    if config.visible_menu_items_only {
+        menu.retain_visible();
+    }
+
+    Ok(menu)
+}
+
+/// Sends an event such as `clicked` or `opened` to a menu item.
+///
+/// # Panics
+///
+/// If the system time is somehow before the Unix epoch.
+async fn send_menu_event(proxy: &DBusMenuProxy<'_>, id: i32, event_id: &str) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time should flow forwards");
+
+    proxy
+        .event(id, event_id, &Value::I32(0), timestamp.as_secs() as u32)
+        .await?;
+
+    Ok(())
+}
+
+/// Logs a signal received from `destination` along with its full payload.
+fn log_raw_signal(destination: &str, signal: &Message) {
+    let header = signal.header();
+    let member = header
+        .member()
+        .map_or("<unknown>", |member| member.as_str());
+    let path = header.path().map_or("<unknown>", |path| path.as_str());
+
+    let body = signal.body();
+    let signature = body.signature().to_string();
+    let payload = match body.deserialize::<Structure>() {
+        Ok(payload) => payload.to_string(),
+        Err(_) if signature.is_empty() => String::from("()"),
+        Err(_) => format!("<{signature}>"),
+    };
+
+    debug!(target: RAW_TARGET, "[{destination}{path}] signal {member}: {payload}");
+}
+
+/// Reduces a burst of signals to the last of each member,
+/// so that each changed property is only fetched once.
+///
+/// The relative order of the remaining signals is kept.
+fn coalesce_signals(signals: Vec<Message>) -> Vec<Message> {
+    let mut coalesced: Vec<Message> = Vec::with_capacity(signals.len());
+
+    for signal in signals {
+        let member = signal.header().member().cloned();
+        coalesced.retain(|other| other.header().member() != member.as_ref());
+        coalesced.push(signal);
+    }
+
+    coalesced
+}
+
+/// Gets the name of the property whose value changed
+/// for an SNI `New*` signal member.
+fn property_name_for_signal(member: &str) -> Option<&str> {
+    match member {
+        "NewAttentionIcon" => Some("AttentionIconName"),
+        "NewIcon" => Some("IconName"),
+        "NewOverlayIcon" => Some("OverlayIconName"),
+        "NewStatus" => Some("Status"),
+        "NewTitle" => Some("Title"),
+        "NewToolTip" => Some("ToolTip"),
+        _ => member.strip_prefix("New"),
+    }
+}
+
+/// Creates the update event for a pixmap property.
+type PixmapUpdate = fn(Option<Vec<IconPixmap>>) -> UpdateEvent;
+
+/// Gets the pixmap property announced by an icon signal `member`,
+/// along with the update event for its value.
+fn pixmap_property_for_signal(member: &str) -> Option<(&'static str, PixmapUpdate)> {
+    match member {
+        "NewIcon" => Some(("IconPixmap", UpdateEvent::IconPixmap)),
+        "NewOverlayIcon" => Some(("OverlayIconPixmap", UpdateEvent::OverlayIconPixmap)),
+        "NewAttentionIcon" => Some(("AttentionIconPixmap", UpdateEvent::AttentionIconPixmap)),
+        _ => None,
+    }
+}
+
+/// Gets the update event from the arguments of a signal `member`,
+/// for the signals which carry the new value.
+///
+/// This allows items to be updated without the `Properties` interface.
+fn update_event_from_signal(change: &Message, member: &str) -> Option<UpdateEvent> {
+    match member {
+        "NewStatus" => change
+            .body()
+            .deserialize::<&str>()
+            .ok()
+            .map(|status| UpdateEvent::Status(item::Status::from(status))),
+        _ => None,
+    }
+}
+
+/// Converts the freshly fetched value of a property
+/// into the update event for the signal `member` that announced the change.
+///
+/// This does not touch the bus,
+/// so signal sequences can be fed through it directly.
+fn update_event_from_property(member: &str, property: &OwnedValue) -> Result<Option<UpdateEvent>> {
+    use UpdateEvent::*;
+    Ok(match member {
+        "NewAttentionIcon" => Some(AttentionIcon(property.to_string().ok())),
+        "NewIcon" => Some(Icon(property.to_string().ok())),
+        "NewOverlayIcon" => Some(OverlayIcon(property.to_string().ok())),
+        "NewStatus" => Some(Status(
+            property.downcast_ref::<&str>().map(item::Status::from)?,
+        )),
+        "NewTitle" => Some(Title(property.to_string().ok())),
+        "NewToolTip" => Some(Tooltip({
+            property
+                .downcast_ref::<&Structure>()
+                .ok()
+                .map(crate::item::Tooltip::try_from)
+                .transpose()?
+        })),
+        _ => {
+            warn!("received unhandled update event: {member}");
+            None
+        }
+    })
+}
+
+/// Gets the ID of the process owning the bus name `name`, if known.
+async fn process_id(dbus_proxy: &DBusProxy<'_>, name: &str) -> Option<u32> {
+    let name = BusName::try_from(name).ok()?;
+    dbus_proxy.get_connection_unix_process_id(name).await.ok()
+}
+
+/// Resolves the unique name (for example `:1.52`) currently owning `destination`.
+///
+/// Items may register using a well-known name
+/// while their signals are sent from the unique name instead.
+async fn resolve_unique_name(destination: &str, connection: &Connection) -> Result<String> {
+    let name = BusName::try_from(destination).map_err(zbus::Error::from)?;
+
+    if let BusName::Unique(unique) = name {
+        Ok(unique.to_string())
+    } else {
+        let dbus_proxy = DBusProxy::new(connection).await?;
+        let owner = dbus_proxy.get_name_owner(name).await?;
+
+        debug!("resolved {destination} to {owner}");
+        Ok(owner.to_string())
+    }
 }
 
-fn parse_address(address: &str) -> (&str, String) {
-    address
+/// Splits an item address into its bus name and object path,
+/// which defaults to the standard path if the address has none.
+///
+/// Addresses are passed on by the watcher, which may not check them,
+/// so both parts are validated before any proxy is built from them.
+///
+/// Items which register just an object path are rejected.
+/// Only the watcher knows which connection made the registration,
+/// so it has to add the bus name itself.
+fn parse_address(address: &str) -> Result<(&str, String)> {
+    let invalid =
+        |err: &dyn std::fmt::Display| Error::InvalidAddress(address.to_string(), err.to_string());
+
+    if address.starts_with('/') {
+        return Err(invalid(&"object path without a bus name"));
+    }
+
+    let (name, path) = address
         .split_once('/')
         .map_or((address, String::from("/StatusNotifierItem")), |(d, p)| {
             (d, format!("/{p}"))
-        })
+        });
+
+    BusName::try_from(name).map_err(|err| invalid(&err))?;
+    ObjectPath::try_from(path.as_str()).map_err(|err| invalid(&err))?;
+
+    Ok((name, path))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
 
     #[test]
     fn parse_unnamed() {
         let address = ":1.58/StatusNotifierItem";
-        let (destination, path) = parse_address(address);
+        let (destination, path) = parse_address(address).expect("address should be valid");
 
         assert_eq!(":1.58", destination);
         assert_eq!("/StatusNotifierItem", path);
@@ -691,9 +4551,283 @@ mod tests {
     #[test]
     fn parse_named() {
         let address = ":1.72/org/ayatana/NotificationItem/dropbox_client_1398";
-        let (destination, path) = parse_address(address);
+        let (destination, path) = parse_address(address).expect("address should be valid");
 
         assert_eq!(":1.72", destination);
         assert_eq!("/org/ayatana/NotificationItem/dropbox_client_1398", path);
     }
+
+    #[test]
+    fn parse_well_known() {
+        let address = "org.kde.StatusNotifierItem-2871-1/StatusNotifierItem";
+        let (destination, path) = parse_address(address).expect("address should be valid");
+
+        assert_eq!("org.kde.StatusNotifierItem-2871-1", destination);
+        assert_eq!("/StatusNotifierItem", path);
+
+        assert!(matches!(
+            BusName::try_from(destination),
+            Ok(BusName::WellKnown(_))
+        ));
+    }
+
+    #[test]
+    fn parse_object_path() {
+        let address = "/org/ayatana/NotificationItem/dropbox_client_1398";
+        assert!(matches!(
+            parse_address(address),
+            Err(Error::InvalidAddress(..))
+        ));
+    }
+
+    #[test]
+    fn introspection_data() {
+        let xml = r#"<node>
+            <interface name="org.freedesktop.DBus.Peer">
+                <method name="Ping"/>
+            </interface>
+            <interface name='org.kde.StatusNotifierItem'>
+                <method name="ContextMenu">
+                    <arg name="x" type="i" direction="in"/>
+                </method>
+                <method name="Activate"/>
+                <signal name="NewIcon"/>
+                <property name="Id" type="s" access="read"/>
+            </interface>
+            <node name="menu"/>
+        </node>"#;
+
+        let interfaces = parse_interfaces(xml);
+
+        assert_eq!(2, interfaces.len());
+        assert_eq!(
+            Some(&InterfaceMembers {
+                methods: vec!["ContextMenu".to_string(), "Activate".to_string()],
+                signals: vec!["NewIcon".to_string()],
+            }),
+            interfaces.get("org.kde.StatusNotifierItem")
+        );
+        assert_eq!(
+            Some(&InterfaceMembers {
+                methods: vec!["Ping".to_string()],
+                signals: vec![],
+            }),
+            interfaces.get("org.freedesktop.DBus.Peer")
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for address in [
+            "",
+            "not a name",
+            ":1.58/Status Notifier",
+            ":1.58//StatusNotifierItem",
+        ] {
+            assert!(
+                matches!(parse_address(address), Err(Error::InvalidAddress(..))),
+                "{address}"
+            );
+        }
+    }
+
+    #[test]
+    fn signal_property_names() {
+        assert_eq!(Some("IconName"), property_name_for_signal("NewIcon"));
+        assert_eq!(Some("ToolTip"), property_name_for_signal("NewToolTip"));
+        assert_eq!(Some("Foo"), property_name_for_signal("NewFoo"));
+        assert_eq!(None, property_name_for_signal("Foo"));
+    }
+
+    #[test]
+    fn update_event_sequence() {
+        let signals: [(&str, Value); 4] = [
+            ("NewIcon", Value::from("firefox")),
+            ("NewStatus", Value::from("NeedsAttention")),
+            ("NewTitle", Value::from("Firefox")),
+            (
+                "NewToolTip",
+                Value::from((
+                    "icon",
+                    Vec::<(i32, i32, Vec<u8>)>::new(),
+                    "title",
+                    "description",
+                )),
+            ),
+        ];
+
+        let events = signals
+            .into_iter()
+            .map(|(member, value)| {
+                let value = OwnedValue::try_from(value).expect("value should be ownable");
+                update_event_from_property(member, &value)
+            })
+            .collect::<Result<Vec<_>>>()
+            .expect("properties should parse");
+
+        assert!(matches!(&events[0], Some(UpdateEvent::Icon(Some(icon))) if icon == "firefox"));
+        assert!(matches!(
+            events[1],
+            Some(UpdateEvent::Status(Status::NeedsAttention))
+        ));
+        assert!(matches!(&events[2], Some(UpdateEvent::Title(Some(title))) if title == "Firefox"));
+        assert!(matches!(
+            &events[3],
+            Some(UpdateEvent::Tooltip(Some(tooltip))) if tooltip.title == "title" && tooltip.icon_data.is_empty()
+        ));
+    }
+
+    #[test]
+    fn update_event_signal_args() {
+        let signal = Message::signal("/StatusNotifierItem", PROPERTIES_INTERFACE, "NewStatus")
+            .and_then(|builder| builder.build(&("Passive",)))
+            .expect("signal should build");
+
+        assert!(matches!(
+            update_event_from_signal(&signal, "NewStatus"),
+            Some(UpdateEvent::Status(Status::Passive))
+        ));
+
+        let signal = Message::signal("/StatusNotifierItem", PROPERTIES_INTERFACE, "NewIcon")
+            .and_then(|builder| builder.build(&()))
+            .expect("signal should build");
+
+        assert!(update_event_from_signal(&signal, "NewIcon").is_none());
+    }
+
+    #[test]
+    fn update_event_wrong_type() {
+        let value = OwnedValue::from(5u32);
+        assert!(update_event_from_property("NewStatus", &value).is_err());
+    }
+
+    #[test]
+    fn coalesce_signal_burst() {
+        let signal = |member| {
+            Message::signal("/StatusNotifierItem", PROPERTIES_INTERFACE, member)
+                .and_then(|builder| builder.build(&()))
+                .expect("signal should build")
+        };
+
+        let mut burst = (0..10).map(|_| signal("NewIcon")).collect::<Vec<_>>();
+        burst.push(signal("NewTitle"));
+        burst.push(signal("NewIcon"));
+
+        let members = coalesce_signals(burst)
+            .iter()
+            .map(|signal| {
+                signal
+                    .header()
+                    .member()
+                    .map(ToString::to_string)
+                    .expect("signal should have member")
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["NewTitle", "NewIcon"], members);
+    }
+
+    #[test]
+    fn rect_anchor() {
+        let rect = Rect {
+            x: 100,
+            y: 0,
+            width: 24,
+            height: 32,
+        };
+
+        assert_eq!((112, 32), rect.anchor());
+    }
+
+    #[test]
+    fn retry_delay() {
+        let policy = RetryPolicy::default();
+
+        for (attempt, full) in [(1, 100), (2, 200), (3, 400), (10, 2000)] {
+            let full = Duration::from_millis(full);
+            let delay = policy.delay(attempt);
+            assert!(delay <= full && delay >= full * 3 / 4, "{delay:?}");
+        }
+
+        let err = Error::ZBusFdo(zbus::fdo::Error::NoReply(String::new()));
+        assert!(is_transient(&err));
+        assert!(is_transient(&timeout_error("GetLayout")));
+        assert!(!is_transient(&Error::InvalidData("bad")));
+    }
+
+    #[tokio::test]
+    async fn call_timeouts() {
+        let config = Client::builder()
+            .call_timeout(Duration::from_secs(2))
+            .item_config(
+                "slow",
+                ItemConfig::new().call_timeout(Duration::from_secs(30)),
+            )
+            .config;
+
+        assert_eq!(Duration::from_secs(2), config.call_timeout("fast"));
+        assert_eq!(Duration::from_secs(30), config.call_timeout("slow"));
+
+        let clock = ManualClock::new();
+        let mut result = std::pin::pin!(with_timeout(
+            &clock,
+            Duration::from_secs(10),
+            "GetLayout",
+            std::future::pending::<zbus::Result<()>>(),
+        ));
+        assert!(poll_once(result.as_mut()).await.is_none());
+
+        clock.advance(Duration::from_secs(10));
+        let result = result.await;
+        assert!(matches!(
+            result,
+            Err(Error::ZBusFdo(zbus::fdo::Error::Timeout(_)))
+        ));
+    }
+
+    #[test]
+    fn lock_recovers_from_poison() {
+        let items = Arc::new(Mutex::new(State::new()));
+
+        let poisoned = items.clone();
+        let _ = std::thread::spawn(move || {
+            let _items = poisoned.lock();
+            panic!("poisoning items");
+        })
+        .join();
+
+        assert!(items.is_poisoned());
+
+        let item = StatusNotifierItem::try_from(DBusProps(HashMap::from([(
+            String::from("Id"),
+            OwnedValue::from(zbus::zvariant::Str::from("app")),
+        )])))
+        .expect("item should parse");
+
+        state::apply(&mut lock(&items), &Event::Add(":1.1".into(), item.into()));
+        assert_eq!(1, snapshot(&items).len());
+    }
+
+    #[test]
+    fn liveness_errors() {
+        use zbus::fdo::Error;
+
+        assert!(is_unresponsive(&Error::NoReply(String::new())));
+        assert!(is_unresponsive(&Error::ServiceUnknown(String::new())));
+        assert!(!is_unresponsive(&Error::UnknownProperty(String::new())));
+        assert!(!is_unresponsive(&Error::AccessDenied(String::new())));
+
+        assert!(lacks_properties_interface(&Error::UnknownMethod(
+            String::new()
+        )));
+        assert!(!lacks_properties_interface(&Error::UnknownProperty(
+            String::new()
+        )));
+
+        let disconnected = zbus::Error::FDO(Box::new(Error::NameHasNoOwner(String::new())));
+        assert!(is_disconnected(&disconnected));
+        assert!(!is_disconnected(&zbus::Error::FDO(Box::new(
+            Error::NoReply(String::new())
+        ))));
+    }
 }
@@ -0,0 +1,235 @@
+use futures_lite::FutureExt;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[cfg(any(test, feature = "test-util"))]
+pub use manual::ManualClock;
+
+/// A future which completes once a [`Clock`] has moved on by the requested duration.
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The source of time for the client's delays, timeouts and periodic checks,
+/// such as the menu update window, retries, cooldowns and liveness checks.
+///
+/// Durations measured only for [`Stats`](crate::client::Stats) use real time.
+pub trait Clock: Debug + Send + Sync {
+    /// Gets the current time.
+    fn now(&self) -> Instant;
+
+    /// Waits until `duration` has passed.
+    fn sleep(&self, duration: Duration) -> Sleep;
+
+    /// Gets the time passed since `earlier`, or zero if it is in the future.
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+}
+
+/// The clock used by default, following Tokio's time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Awaits `future`, giving up with `None` once `limit` has passed on `clock`.
+pub(crate) async fn timeout<F: Future>(
+    clock: &dyn Clock,
+    limit: Duration,
+    future: F,
+) -> Option<F::Output> {
+    async { Some(future.await) }
+        .or(async {
+            clock.sleep(limit).await;
+            None
+        })
+        .await
+}
+
+/// Ticks every `period` on a clock.
+///
+/// A tick which is late delays the ones after it, rather than several firing at once.
+#[derive(Debug)]
+pub(crate) struct Ticker {
+    clock: Arc<dyn Clock>,
+    next: Instant,
+    period: Duration,
+}
+
+impl Ticker {
+    /// Creates a ticker whose first tick is at `start`.
+    pub(crate) fn new(clock: Arc<dyn Clock>, start: Instant, period: Duration) -> Self {
+        Self {
+            clock,
+            next: start,
+            period,
+        }
+    }
+
+    /// Waits for the next tick.
+    ///
+    /// This is safe to cancel, as the ticker only moves on once a tick has passed.
+    pub(crate) async fn tick(&mut self) {
+        let remaining = self.next.saturating_duration_since(self.clock.now());
+        if !remaining.is_zero() {
+            self.clock.sleep(remaining).await;
+        }
+
+        self.next = self.clock.now() + self.period;
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+mod manual {
+    use super::{Clock, Sleep};
+    use crate::sync::lock;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    /// A clock which only moves when advanced, for testing time-based behaviour
+    /// without waiting on real time.
+    ///
+    /// Clones share the same time, so one can be given to the client
+    /// while the test keeps another to advance it.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use system_tray::client::Client;
+    /// use system_tray::clock::ManualClock;
+    ///
+    /// # async fn run() -> system_tray::error::Result<()> {
+    /// let clock = ManualClock::new();
+    /// let client = Client::builder().clock(clock.clone()).build().await?;
+    ///
+    /// // skip past the cooldown on `AboutToShow`
+    /// clock.advance(Duration::from_secs(1));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct ManualClock {
+        time: Arc<Mutex<ManualTime>>,
+    }
+
+    #[derive(Debug)]
+    struct ManualTime {
+        now: Instant,
+        sleepers: Vec<Waker>,
+    }
+
+    impl Default for ManualClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl ManualClock {
+        /// Creates a clock stopped at the current time.
+        #[must_use]
+        pub fn new() -> Self {
+            Self {
+                time: Arc::new(Mutex::new(ManualTime {
+                    now: Instant::now(),
+                    sleepers: vec![],
+                })),
+            }
+        }
+
+        /// Moves the clock on by `duration`, waking any sleeps which have finished.
+        pub fn advance(&self, duration: Duration) {
+            let sleepers = {
+                let mut time = lock(&self.time);
+                time.now += duration;
+                std::mem::take(&mut time.sleepers)
+            };
+
+            // sleeps which have not finished register themselves again when polled
+            for sleeper in sleepers {
+                sleeper.wake();
+            }
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            lock(&self.time).now
+        }
+
+        fn sleep(&self, duration: Duration) -> Sleep {
+            Box::pin(ManualSleep {
+                time: self.time.clone(),
+                deadline: self.now() + duration,
+            })
+        }
+    }
+
+    /// A sleep on a [`ManualClock`].
+    struct ManualSleep {
+        time: Arc<Mutex<ManualTime>>,
+        deadline: Instant,
+    }
+
+    impl Future for ManualSleep {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut time = lock(&self.time);
+            if time.now >= self.deadline {
+                return Poll::Ready(());
+            }
+
+            if !time
+                .sleepers
+                .iter()
+                .any(|sleeper| sleeper.will_wake(cx.waker()))
+            {
+                time.sleepers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future::poll_once;
+
+    #[tokio::test]
+    async fn manual_clock() {
+        let clock = ManualClock::new();
+        let started = clock.now();
+
+        let mut sleep = clock.sleep(Duration::from_secs(5));
+        assert!(poll_once(&mut sleep).await.is_none());
+
+        clock.advance(Duration::from_secs(4));
+        assert!(poll_once(&mut sleep).await.is_none());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(poll_once(&mut sleep).await.is_some());
+        assert_eq!(Duration::from_secs(5), clock.elapsed(started));
+
+        let mut ticker = Ticker::new(Arc::new(clock.clone()), clock.now(), Duration::from_secs(2));
+        assert!(poll_once(ticker.tick()).await.is_some());
+        assert!(poll_once(ticker.tick()).await.is_none());
+
+        clock.advance(Duration::from_secs(2));
+        assert!(poll_once(ticker.tick()).await.is_some());
+    }
+}
@@ -0,0 +1,293 @@
+use crate::client::Client;
+use crate::error::Result;
+use crate::menu::{MenuItem, TrayMenu};
+
+/// Keyboard navigation through the cached menu of an item.
+///
+/// The cursor tracks the selected menu item and the chain of submenus opened to reach it,
+/// sending the `hovered`, `opened`, `closed` and `clicked` events
+/// an application expects as the user moves through its menu.
+///
+/// Items are tracked by id, so the cursor stays in place when the layout is updated.
+/// Only items which are visible, enabled and not separators can be selected.
+///
+/// ```no_run
+/// use system_tray::client::Client;
+/// use system_tray::cursor::MenuCursor;
+///
+/// # async fn run(client: &Client, address: &str) -> system_tray::error::Result<()> {
+/// client.open_menu(address).await?;
+///
+/// let mut cursor = MenuCursor::new(address);
+/// cursor.next(client).await?;
+/// cursor.enter(client).await?;
+/// cursor.activate(client).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MenuCursor {
+    address: String,
+    /// The ids of the menu items whose submenus are open, outermost first.
+    parents: Vec<i32>,
+    selected: Option<i32>,
+}
+
+impl MenuCursor {
+    /// Creates a cursor at the top of the root menu of the item at `address`,
+    /// with nothing selected.
+    #[must_use]
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            parents: vec![],
+            selected: None,
+        }
+    }
+
+    /// Gets the id of the selected menu item.
+    #[must_use]
+    pub fn selected(&self) -> Option<i32> {
+        self.selected
+    }
+
+    /// Gets the ids of the menu items whose submenus are open, outermost first.
+    #[must_use]
+    pub fn parents(&self) -> &[i32] {
+        &self.parents
+    }
+
+    /// Gets a copy of the selected menu item.
+    #[must_use]
+    pub fn current(&self, client: &Client) -> Option<MenuItem> {
+        let id = self.selected?;
+        client.with_menu_layout(&self.address, |menu| menu?.find_item(id).cloned())
+    }
+
+    /// Selects the next item in the current menu, wrapping around at the end.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the `hovered` event fails.
+    pub async fn next(&mut self, client: &Client) -> Result<Option<MenuItem>> {
+        self.step(client, true).await
+    }
+
+    /// Selects the previous item in the current menu, wrapping around at the start.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the `hovered` event fails.
+    pub async fn prev(&mut self, client: &Client) -> Result<Option<MenuItem>> {
+        self.step(client, false).await
+    }
+
+    /// Selects the next item in the current menu with the access key `mnemonic`,
+    /// ignoring case.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the `hovered` event fails.
+    pub async fn find_mnemonic(
+        &mut self,
+        client: &Client,
+        mnemonic: char,
+    ) -> Result<Option<MenuItem>> {
+        let id = self.with_siblings(client, |items| {
+            find_mnemonic(items, self.selected, mnemonic)
+        });
+
+        self.select(client, id).await
+    }
+
+    /// Opens the submenu of the selected item, selecting its first item.
+    ///
+    /// Returns `None` without moving if the selected item has no submenu.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if opening the submenu fails.
+    pub async fn enter(&mut self, client: &Client) -> Result<Option<MenuItem>> {
+        let Some(parent) = self.current(client).filter(MenuItem::has_submenu) else {
+            return Ok(None);
+        };
+
+        let menu = client.open_submenu(&self.address, parent.id).await?;
+        let id = menu
+            .find_item(parent.id)
+            .and_then(|parent| step(&parent.submenu, None, true));
+
+        self.parents.push(parent.id);
+        self.selected = None;
+        self.select(client, id).await
+    }
+
+    /// Closes the innermost open submenu, selecting the item it belongs to again.
+    ///
+    /// Returns `false` without moving if no submenu is open.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the `closed` event fails.
+    pub async fn leave(&mut self, client: &Client) -> Result<bool> {
+        let Some(parent) = self.parents.pop() else {
+            return Ok(false);
+        };
+
+        self.selected = Some(parent);
+        client
+            .send_menu_item_event(&self.address, parent, "closed")
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Activates the selected item,
+    /// entering its submenu if it has one, or else clicking it.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the event fails.
+    pub async fn activate(&mut self, client: &Client) -> Result<()> {
+        let Some(item) = self.current(client) else {
+            return Ok(());
+        };
+
+        if item.has_submenu() {
+            self.enter(client).await?;
+        } else {
+            client
+                .send_menu_item_event(&self.address, item.id, "clicked")
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn step(&mut self, client: &Client, forward: bool) -> Result<Option<MenuItem>> {
+        let id = self.with_siblings(client, |items| step(items, self.selected, forward));
+        self.select(client, id).await
+    }
+
+    /// Moves to the item `id`, sending it the `hovered` event if that changes the selection.
+    async fn select(&mut self, client: &Client, id: Option<i32>) -> Result<Option<MenuItem>> {
+        let Some(id) = id else {
+            return Ok(None);
+        };
+
+        if self.selected != Some(id) {
+            self.selected = Some(id);
+            client
+                .send_menu_item_event(&self.address, id, "hovered")
+                .await?;
+        }
+
+        Ok(self.current(client))
+    }
+
+    /// Calls `f` with the items of the innermost open menu.
+    fn with_siblings<R: Default>(&self, client: &Client, f: impl FnOnce(&[MenuItem]) -> R) -> R {
+        client.with_menu_layout(&self.address, |menu| {
+            menu.and_then(|menu| siblings(menu, self.parents.last().copied()))
+                .map(f)
+                .unwrap_or_default()
+        })
+    }
+}
+
+/// Gets the items of the submenu of `parent`, or of the root menu.
+fn siblings(menu: &TrayMenu, parent: Option<i32>) -> Option<&[MenuItem]> {
+    match parent {
+        Some(parent) => menu.find_item(parent).map(|item| item.submenu.as_slice()),
+        None => Some(&menu.submenus),
+    }
+}
+
+fn is_selectable(item: &MenuItem) -> bool {
+    item.visible && item.enabled && !item.is_separator()
+}
+
+/// Finds the selectable item after (or before) `selected`, wrapping around.
+/// With nothing selected, this is the first (or last) selectable item.
+fn step(items: &[MenuItem], selected: Option<i32>, forward: bool) -> Option<i32> {
+    let start = selected.and_then(|id| items.iter().position(|item| item.id == id));
+    let len = items.len();
+
+    (1..=len)
+        .map(|offset| match (start, forward) {
+            (Some(start), true) => (start + offset) % len,
+            (Some(start), false) => (start + len - offset) % len,
+            (None, true) => offset - 1,
+            (None, false) => len - offset,
+        })
+        .map(|index| &items[index])
+        .find(|item| is_selectable(item))
+        .map(|item| item.id)
+}
+
+/// Finds the next selectable item after `selected` with the access key `mnemonic`.
+fn find_mnemonic(items: &[MenuItem], selected: Option<i32>, mnemonic: char) -> Option<i32> {
+    let start = selected
+        .and_then(|id| items.iter().position(|item| item.id == id))
+        .map_or(0, |index| index + 1);
+
+    items[start..]
+        .iter()
+        .chain(&items[..start])
+        .filter(|item| is_selectable(item))
+        .find(|item| {
+            item.mnemonic
+                .is_some_and(|char| char.to_lowercase().eq(mnemonic.to_lowercase()))
+        })
+        .map(|item| item.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::menu::MenuType;
+
+    fn make_item(id: i32, mnemonic: Option<char>) -> MenuItem {
+        MenuItem {
+            id,
+            mnemonic,
+            enabled: true,
+            visible: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn step_skips_unselectable() {
+        let mut disabled = make_item(2, None);
+        disabled.enabled = false;
+        let separator = MenuItem {
+            id: 3,
+            menu_type: MenuType::Separator,
+            visible: true,
+            ..Default::default()
+        };
+        let items = [make_item(1, None), disabled, separator, make_item(4, None)];
+
+        assert_eq!(Some(1), step(&items, None, true));
+        assert_eq!(Some(4), step(&items, None, false));
+        assert_eq!(Some(4), step(&items, Some(1), true));
+        assert_eq!(Some(1), step(&items, Some(4), true));
+        assert_eq!(Some(4), step(&items, Some(1), false));
+        assert_eq!(None, step(&[], None, true));
+    }
+
+    #[test]
+    fn mnemonic_search() {
+        let items = [
+            make_item(1, Some('S')),
+            make_item(2, Some('Q')),
+            make_item(3, Some('s')),
+        ];
+
+        assert_eq!(Some(1), find_mnemonic(&items, None, 's'));
+        assert_eq!(Some(3), find_mnemonic(&items, Some(1), 's'));
+        assert_eq!(Some(1), find_mnemonic(&items, Some(3), 'S'));
+        assert_eq!(None, find_mnemonic(&items, None, 'x'));
+    }
+}
@@ -31,7 +31,6 @@ pub(crate) struct SubMenuLayout {
     pub submenus: Vec<OwnedValue>,
 }
 
-#[allow(dead_code)]
 type GroupProperties = Vec<(i32, HashMap<String, zbus::zvariant::OwnedValue>)>;
 
 #[derive(Deserialize, Type, Debug, Clone)]
@@ -59,6 +58,8 @@ pub struct RemovedProps<'a> {
 pub trait DBusMenu {
     fn about_to_show(&self, id: i32) -> zbus::Result<bool>;
 
+    fn about_to_show_group(&self, ids: &[i32]) -> zbus::Result<(Vec<i32>, Vec<i32>)>;
+
     fn event(
         &self,
         id: i32,
@@ -95,9 +96,15 @@ pub trait DBusMenu {
     #[zbus(signal)]
     fn layout_updated(&self, revision: u32, parent: i32) -> zbus::Result<()>;
 
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> zbus::Result<Vec<String>>;
+
     #[zbus(property)]
     fn status(&self) -> zbus::Result<String>;
 
+    #[zbus(property)]
+    fn text_direction(&self) -> zbus::Result<String>;
+
     #[zbus(property)]
     fn version(&self) -> zbus::Result<u32>;
 }
@@ -1,7 +1,7 @@
 use crate::error::Result;
 use std::collections::HashMap;
 use std::ops::Deref;
-use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::zvariant::{Dict, ObjectPath, OwnedValue, Value};
 
 pub mod dbus_menu_proxy;
 pub mod notifier_item_proxy;
@@ -41,6 +41,23 @@ impl DBusProps {
     }
 }
 
+/// Gets `key` from an `a{sv}` dictionary if present,
+/// downcasting it to type `V`.
+///
+/// Unlike [`Dict::get`], this takes the key as a plain `&str`,
+/// and returns the crate error type.
+pub(crate) fn dict_get<'a, V>(dict: &'a Dict<'a, 'a>, key: &str) -> Result<Option<V>>
+where
+    V: TryFrom<&'a Value<'a>>,
+    <V as TryFrom<&'a Value<'a>>>::Error: Into<zbus::zvariant::Error>,
+{
+    dict.iter()
+        .find(|(k, _)| matches!(k, Value::Str(k) if k.as_str() == key))
+        .map(|(_, v)| v.downcast_ref::<V>())
+        .transpose()
+        .map_err(Into::into)
+}
+
 pub(crate) trait OwnedValueExt {
     fn to_string(&self) -> Result<String>;
 }
@@ -60,3 +77,67 @@ impl Deref for DBusProps {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::Array;
+
+    fn sample_dict() -> Value<'static> {
+        let mut map = HashMap::<&str, Value>::new();
+        map.insert("label", Value::from("_Quit"));
+        map.insert("enabled", Value::from(false));
+        map.insert("toggle-state", Value::from(1i32));
+        map.insert("icon-data", Value::from(vec![1u8, 2, 3]));
+
+        Value::from(map)
+    }
+
+    #[test]
+    fn dict_get_present() {
+        let value = sample_dict();
+        let Value::Dict(dict) = &value else {
+            panic!("expected dict");
+        };
+
+        assert_eq!(
+            Some("_Quit"),
+            dict_get::<&str>(dict, "label").ok().flatten()
+        );
+        assert_eq!(
+            Some(false),
+            dict_get::<bool>(dict, "enabled").ok().flatten()
+        );
+        assert_eq!(
+            Some(1),
+            dict_get::<i32>(dict, "toggle-state").ok().flatten()
+        );
+        assert_eq!(
+            Some(3),
+            dict_get::<&Array>(dict, "icon-data")
+                .ok()
+                .flatten()
+                .map(Array::len)
+        );
+    }
+
+    #[test]
+    fn dict_get_missing() {
+        let value = sample_dict();
+        let Value::Dict(dict) = &value else {
+            panic!("expected dict");
+        };
+
+        assert!(matches!(dict_get::<&str>(dict, "icon-name"), Ok(None)));
+    }
+
+    #[test]
+    fn dict_get_wrong_type() {
+        let value = sample_dict();
+        let Value::Dict(dict) = &value else {
+            panic!("expected dict");
+        };
+
+        assert!(dict_get::<i32>(dict, "label").is_err());
+    }
+}
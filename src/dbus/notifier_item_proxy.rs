@@ -23,6 +23,9 @@ pub trait StatusNotifierItem {
     /// ContextMenu method
     fn context_menu(&self, x: i32, y: i32) -> zbus::Result<()>;
 
+    /// ProvideXdgActivationToken method
+    fn provide_xdg_activation_token(&self, token: &str) -> zbus::Result<()>;
+
     /// Scroll method
     fn scroll(&self, delta: i32, orientation: &str) -> zbus::Result<()>;
 
@@ -1,4 +1,5 @@
 use crate::names;
+use crate::sync::lock;
 use std::borrow::Cow;
 use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
@@ -47,7 +48,7 @@ impl StatusNotifierWatcher {
 
         let added_first = {
             // scoped around locking of hosts
-            let mut hosts = self.hosts.lock().expect("mutex lock should succeed");
+            let mut hosts = lock(&self.hosts);
             if !hosts.insert(service.to_string()) {
                 // we're already tracking them
                 return Ok(());
@@ -72,7 +73,7 @@ impl StatusNotifierWatcher {
                 info!("lost host: {}", service);
 
                 let removed_last = {
-                    let mut hosts = hosts.lock().expect("mutex lock should succeed");
+                    let mut hosts = lock(&hosts);
                     let did_remove = hosts.remove(service.as_str());
                     did_remove && hosts.is_empty()
                 };
@@ -113,7 +114,7 @@ impl StatusNotifierWatcher {
     /// IsStatusNotifierHostRegistered property
     #[zbus(property)]
     fn is_status_notifier_host_registered(&self) -> bool {
-        let hosts = self.hosts.lock().expect("mutex lock should succeed");
+        let hosts = lock(&self.hosts);
         !hosts.is_empty()
     }
 
@@ -131,7 +132,7 @@ impl StatusNotifierWatcher {
         let item = format!("{service}{objpath}");
 
         {
-            let mut items = self.items.lock().expect("mutex lock should succeed");
+            let mut items = lock(&self.items);
             if !items.insert(item.clone()) {
                 // we're already tracking them
                 info!("new item: {} (duplicate)", item);
@@ -156,7 +157,7 @@ impl StatusNotifierWatcher {
                 debug!("gone item: {}", &item);
 
                 {
-                    let mut items = items.lock().expect("mutex lock should succeed");
+                    let mut items = lock(&items);
                     items.remove(&item);
                 }
 
@@ -194,10 +195,7 @@ impl StatusNotifierWatcher {
 
         let item = format!("{service}{objpath}");
 
-        self.items
-            .lock()
-            .expect("mutex lock should succeed")
-            .remove(&item);
+        lock(&self.items).remove(&item);
 
         if let Err(err) = Self::status_notifier_item_unregistered(&context, &item).await {
             error!("{err:?}");
@@ -223,7 +221,7 @@ impl StatusNotifierWatcher {
     /// RegisteredStatusNotifierItems property
     #[zbus(property)]
     fn registered_status_notifier_items(&self) -> Vec<String> {
-        let items = self.items.lock().expect("mutex lock should succeed");
+        let items = lock(&self.items);
         items.iter().cloned().collect()
     }
 
@@ -310,7 +308,18 @@ async fn parse_service<'a>(
             Err(zbus::fdo::Error::InvalidArgs("Unknown bus address".into()))
         }
     } else {
-        // parse the bus name they gave us
+        // parse the bus name they gave us, along with the object path if they gave one
+        let (service, objpath) = service
+            .find('/')
+            .map_or((service, names::ITEM_OBJECT), |index| {
+                service.split_at(index)
+            });
+
+        if let Err(e) = zbus::zvariant::ObjectPath::try_from(objpath) {
+            warn!("received invalid object path {:?}: {}", objpath, e);
+            return Err(zbus::fdo::Error::InvalidArgs(e.to_string()));
+        }
+
         let busname: zbus::names::BusName = match service.try_into() {
             Ok(x) => x,
             Err(e) => {
@@ -320,14 +329,14 @@ async fn parse_service<'a>(
         };
 
         if let zbus::names::BusName::Unique(unique) = busname {
-            Ok((unique.to_owned(), names::ITEM_OBJECT))
+            Ok((unique.to_owned(), objpath))
         } else {
             // they gave us a "well-known name" like org.kde.StatusNotifierHost-81830-0, we need to
             // convert this into the actual identifier for their bus (e.g. :1.234), so that even if
             // they remove that well-known name it's fine.
             let dbus = zbus::fdo::DBusProxy::new(con).await?;
             match dbus.get_name_owner(busname).await {
-                Ok(owner) => Ok((owner.into_inner(), names::ITEM_OBJECT)),
+                Ok(owner) => Ok((owner.into_inner(), objpath)),
                 Err(e) => {
                     warn!("failed to get owner of {:?}: {}", service, e);
                     Err(e)
@@ -18,4 +18,17 @@ pub enum Error {
     ZBusVariant(#[from] zbus::zvariant::Error),
     #[error("invalid data error")]
     InvalidData(&'static str),
+    #[error("no item registered at address {0}")]
+    UnknownItem(String),
+    #[error("invalid item address {0}: {1}")]
+    InvalidAddress(String, String),
+    #[error("menu layout is nested more than {0} levels deep")]
+    MenuTooDeep(usize),
+    #[error("menu item id {0} appears more than once in the layout")]
+    DuplicateMenuId(i32),
+    #[error("failed to access the fixture file")]
+    Fixture(#[source] std::io::Error),
+    #[cfg(feature = "test-util")]
+    #[error("failed to start the test bus")]
+    TestBus(#[source] std::io::Error),
 }
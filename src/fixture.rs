@@ -0,0 +1,432 @@
+use crate::client::{Event, UpdateEvent};
+use crate::dbus::dbus_menu_proxy::{MenuLayout, PropertiesUpdate};
+use crate::dbus::DBusProps;
+use crate::error::{Error, Result};
+use crate::item::{Status, StatusNotifierItem};
+use crate::menu::{MenuDiff, TrayMenu};
+use crate::state::{self, State};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Write as _};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc;
+use tracing::error;
+use zbus::message::Type;
+use zbus::zvariant::serialized::{Context, Data};
+use zbus::zvariant::{Endian, OwnedValue};
+use zbus::Message;
+
+/// The smallest possible message, a primary header with no fields.
+const MIN_MESSAGE_SIZE: usize = 16;
+
+/// The largest message the bus allows.
+const MAX_MESSAGE_SIZE: usize = 128 * 1024 * 1024;
+
+/// The size of the primary header, before the length of the header fields.
+const PRIMARY_HEADER_SIZE: usize = 12;
+
+/// The only version of the message format.
+const PROTOCOL_VERSION: u8 = 1;
+
+/// The code of the header field giving the number of file descriptors sent with a message.
+const UNIX_FDS_FIELD: u8 = 9;
+
+/// A sequence of messages received from the bus,
+/// as recorded by [`ClientBuilder::capture`](crate::client::ClientBuilder::capture).
+///
+/// Fixtures are stored as text, with each message on its own line as hex-encoded bytes.
+/// Blank lines and lines starting with `#` are ignored,
+/// so recordings can be annotated before being checked in.
+///
+/// ```no_run
+/// use system_tray::fixture::Fixture;
+/// use system_tray::state::TrayState;
+///
+/// # fn run() -> system_tray::error::Result<()> {
+/// let fixture = Fixture::load("tests/fixtures/broken-menu.txt")?;
+/// let mut state = TrayState::new();
+///
+/// for event in fixture.replay() {
+///     state.apply(&event);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Fixture {
+    messages: Vec<Message>,
+}
+
+impl Fixture {
+    /// Creates an empty fixture.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the fixture stored at `path`.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if the file cannot be read,
+    /// or any line is not a valid message.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        std::fs::read_to_string(path)
+            .map_err(Error::Fixture)?
+            .parse()
+    }
+
+    /// Gets the recorded messages, in the order received.
+    #[must_use]
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Adds `message` to the end of the fixture.
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Feeds the messages through the client's parsing,
+    /// returning the events the client would send for them.
+    ///
+    /// Without a bus to call back into, only messages which carry their data are understood:
+    ///
+    /// - `GetAll` replies with item properties add the item, or update it after the first.
+    /// - `GetLayout` replies and `ItemsPropertiesUpdated` signals update its menu.
+    /// - `NewStatus` signals update its status.
+    /// - The item's name leaving the bus removes it.
+    ///
+    /// Items are keyed on the sender of the messages.
+    /// Other messages, and any which fail to parse, are skipped.
+    #[must_use]
+    pub fn replay(&self) -> Vec<Event> {
+        let mut items = State::new();
+        let mut events = vec![];
+
+        for message in &self.messages {
+            for event in replay_message(message, &items) {
+                state::apply(&mut items, &event);
+                events.push(event);
+            }
+        }
+
+        events
+    }
+}
+
+impl FromStr for Fixture {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let messages = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(decode_message)
+            .collect::<Result<_>>()?;
+
+        Ok(Self { messages })
+    }
+}
+
+impl Display for Fixture {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for message in &self.messages {
+            writeln!(f, "{}", encode_message(message))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends each message received to a fixture file.
+///
+/// Messages are written from a thread of their own,
+/// so that a slow disk does not hold up the client.
+pub(crate) struct Recorder {
+    tx: mpsc::Sender<Message>,
+}
+
+impl Recorder {
+    /// Opens the fixture file at `path` for appending, creating it if missing,
+    /// and starts the thread writing to it.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(Error::Fixture)?;
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(String::from("system-tray-capture"))
+            .spawn(move || {
+                if let Err(err) = write_messages(&rx, BufWriter::new(file)) {
+                    error!("failed to write to the fixture file: {err}");
+                }
+            })
+            .map_err(Error::Fixture)?;
+
+        Ok(Self { tx })
+    }
+
+    /// Queues `message` to be written to the end of the file.
+    pub(crate) fn record(&self, message: Message) -> Result<()> {
+        self.tx.send(message).map_err(|_| {
+            Error::Fixture(std::io::Error::new(
+                ErrorKind::BrokenPipe,
+                "fixture writer stopped",
+            ))
+        })
+    }
+}
+
+/// Writes each message from `rx` to `file` until the recorder is dropped,
+/// flushing whenever no more are waiting.
+fn write_messages(rx: &mpsc::Receiver<Message>, mut file: BufWriter<File>) -> std::io::Result<()> {
+    while let Ok(message) = rx.recv() {
+        writeln!(file, "{}", encode_message(&message))?;
+
+        for message in rx.try_iter() {
+            writeln!(file, "{}", encode_message(&message))?;
+        }
+
+        file.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Gets the events for a single message, given the items seen so far.
+fn replay_message(message: &Message, items: &State) -> Vec<Event> {
+    let header = message.header();
+    let Some(sender) = header.sender().map(ToString::to_string) else {
+        return vec![];
+    };
+    let member = header.member().map(|member| member.as_str());
+    let body = message.body();
+
+    match (message.message_type(), member) {
+        (Type::MethodReturn, _) => {
+            if let Ok(props) = body.deserialize::<HashMap<String, OwnedValue>>() {
+                let Ok(item) = StatusNotifierItem::try_from(DBusProps(props)) else {
+                    return vec![];
+                };
+
+                return match items.get(&sender) {
+                    Some((old, _)) => state::item_updates(old, &item)
+                        .into_iter()
+                        .map(|update| Event::Update(sender.clone(), update))
+                        .collect(),
+                    None => vec![Event::Add(sender, item.into())],
+                };
+            }
+
+            body.deserialize::<MenuLayout>()
+                .ok()
+                .and_then(|layout| TrayMenu::try_from(layout).ok())
+                .map(|menu| vec![Event::Update(sender, UpdateEvent::Menu(menu))])
+                .unwrap_or_default()
+        }
+        (Type::Signal, Some("ItemsPropertiesUpdated")) => body
+            .deserialize::<PropertiesUpdate>()
+            .ok()
+            .and_then(|update| Vec::<MenuDiff>::try_from(update).ok())
+            .map(|diffs| vec![Event::Update(sender, UpdateEvent::MenuDiff(diffs))])
+            .unwrap_or_default(),
+        (Type::Signal, Some("NewStatus")) => body
+            .deserialize::<&str>()
+            .map(|status| {
+                vec![Event::Update(
+                    sender,
+                    UpdateEvent::Status(Status::from(status)),
+                )]
+            })
+            .unwrap_or_default(),
+        (Type::Signal, Some("NameOwnerChanged")) => {
+            let Ok((name, _, new_owner)) = body.deserialize::<(&str, &str, &str)>() else {
+                return vec![];
+            };
+
+            if new_owner.is_empty() && items.contains_key(name) {
+                vec![Event::Remove(name.to_string())]
+            } else {
+                vec![]
+            }
+        }
+        _ => vec![],
+    }
+}
+
+/// Encodes the raw bytes of `message` as hex.
+///
+/// File descriptors sent with the message are not recorded.
+fn encode_message(message: &Message) -> String {
+    let bytes = message.data().bytes();
+
+    let mut line = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(line, "{byte:02x}");
+    }
+
+    line
+}
+
+/// Decodes a message from a line of hex.
+fn decode_message(line: &str) -> Result<Message> {
+    if !line.len().is_multiple_of(2) {
+        return Err(Error::InvalidData(
+            "fixture line has an odd number of digits",
+        ));
+    }
+
+    let bytes = (0..line.len())
+        .step_by(2)
+        .map(|i| {
+            line.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or(Error::InvalidData("fixture line is not hex"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let data = validate_message(bytes)?;
+
+    // SAFETY: the bytes have passed the checks zbus makes on messages read from the socket,
+    // and expect no file descriptors
+    let message = unsafe { Message::from_bytes(data) }?;
+    Ok(message)
+}
+
+/// Checks that `bytes` hold exactly one well-formed message header and its body,
+/// as the bus connection does before handing over a message it has read.
+///
+/// The body itself is checked as it is deserialized.
+fn validate_message(bytes: Vec<u8>) -> Result<Data<'static, 'static>> {
+    if bytes.len() < MIN_MESSAGE_SIZE {
+        return Err(Error::InvalidData("fixture message is too short"));
+    }
+
+    let endian = match bytes[0] {
+        b'l' => Endian::Little,
+        b'B' => Endian::Big,
+        _ => return Err(Error::InvalidData("fixture message has no byte order")),
+    };
+
+    if bytes[3] != PROTOCOL_VERSION {
+        return Err(Error::InvalidData(
+            "fixture message has an unknown protocol version",
+        ));
+    }
+
+    let read_u32 = |offset: usize| {
+        let word = [0, 1, 2, 3].map(|i| bytes[offset + i]);
+        match endian {
+            Endian::Little => u32::from_le_bytes(word),
+            Endian::Big => u32::from_be_bytes(word),
+        }
+    };
+
+    let body_len = read_u32(4) as usize;
+    let header_len = MIN_MESSAGE_SIZE + read_u32(12) as usize;
+    let total_len = header_len.next_multiple_of(8) + body_len;
+    if total_len > MAX_MESSAGE_SIZE || total_len != bytes.len() {
+        return Err(Error::InvalidData(
+            "fixture message length does not match its header",
+        ));
+    }
+
+    let data = Data::new(bytes, Context::new_dbus(endian, 0));
+
+    let (fields, _) = data
+        .slice(PRIMARY_HEADER_SIZE..header_len)
+        .deserialize::<Vec<(u8, OwnedValue)>>()?;
+    if fields.iter().any(|(code, _)| *code == UNIX_FDS_FIELD) {
+        return Err(Error::InvalidData(
+            "fixture message expects file descriptors",
+        ));
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::Value;
+
+    #[test]
+    fn replay_messages() {
+        let call = Message::method_call("/StatusNotifierItem", "GetAll")
+            .expect("path should be valid")
+            .build(&())
+            .expect("call should build");
+
+        let reply = |title: &str| {
+            let props = HashMap::from([
+                ("Id", Value::from("mock")),
+                ("Title", Value::from(title.to_string())),
+            ]);
+
+            Message::method_return(&call.header())
+                .and_then(|builder| builder.sender(":1.5"))
+                .and_then(|builder| builder.build(&(props,)))
+                .expect("reply should build")
+        };
+
+        let status = Message::signal(
+            "/StatusNotifierItem",
+            "org.kde.StatusNotifierItem",
+            "NewStatus",
+        )
+        .and_then(|builder| builder.sender(":1.5"))
+        .and_then(|builder| builder.build(&("NeedsAttention",)))
+        .expect("signal should build");
+
+        let gone = Message::signal(
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameOwnerChanged",
+        )
+        .and_then(|builder| builder.sender("org.freedesktop.DBus"))
+        .and_then(|builder| builder.build(&(":1.5", ":1.5", "")))
+        .expect("signal should build");
+
+        let mut fixture = Fixture::new();
+        fixture.push(reply("first"));
+        fixture.push(reply("second"));
+        fixture.push(status);
+        fixture.push(gone);
+
+        let text = format!("# annotated\n\n{fixture}");
+        let fixture = text.parse::<Fixture>().expect("fixture should parse");
+        assert_eq!(4, fixture.messages().len());
+
+        let events = fixture.replay();
+        assert_eq!(4, events.len());
+        assert!(
+            matches!(&events[0], Event::Add(address, item) if address == ":1.5"
+            && item.title.as_deref() == Some("first"))
+        );
+        assert!(
+            matches!(&events[1], Event::Update(_, UpdateEvent::Title(Some(title)))
+            if title == "second")
+        );
+        assert!(matches!(
+            events[2],
+            Event::Update(_, UpdateEvent::Status(Status::NeedsAttention))
+        ));
+        assert!(matches!(&events[3], Event::Remove(address) if address == ":1.5"));
+
+        assert!("0102".parse::<Fixture>().is_err());
+
+        // messages whose header does not match their length are rejected before parsing
+        let line = text.lines().nth(2).expect("fixture should have messages");
+        let truncated = &line[..line.len() - 2];
+        assert!(truncated.parse::<Fixture>().is_err());
+        let longer_body = format!("{}ff{}", &line[..8], &line[10..]);
+        assert!(longer_body.parse::<Fixture>().is_err());
+    }
+}
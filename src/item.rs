@@ -1,7 +1,11 @@
+use crate::client::{Event, UpdateEvent};
 use crate::dbus::DBusProps;
 use crate::error::{Error, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use tracing::warn;
 use zbus::zvariant::{Array, Structure};
 
 /// Represents an item to display inside the tray.
@@ -16,8 +20,8 @@ pub struct StatusNotifierItem {
     /// The allowed values for the Category property are:
     ///
     /// - `ApplicationStatus`: The item describes the status of a generic application, for instance the current state of a media player.
-    ///     In the case where the category of the item can not be known, such as when the item is being proxied from another incompatible or emulated system,
-    ///     `ApplicationStatus` can be used a sensible default fallback.
+    ///   In the case where the category of the item can not be known, such as when the item is being proxied from another incompatible or emulated system,
+    ///   `ApplicationStatus` can be used a sensible default fallback.
     /// - `Communications`: The item describes the status of communication oriented applications, like an instant messenger or an email client.
     /// - `SystemServices`: The item describes services of the system not seen as a stand alone application by the user, such as an indicator for the activity of a disk indexing service.
     /// - `Hardware`: The item describes the state and control of a particular hardware, such as an indicator of the battery charge or sound card volume control.
@@ -33,7 +37,7 @@ pub struct StatusNotifierItem {
     /// - Passive: The item doesn't convey important information to the user, it can be considered an "idle" status and is likely that visualizations will chose to hide it.
     /// - Active: The item is active, is more important that the item will be shown in some way to the user.
     /// - `NeedsAttention`: The item carries really important information for the user, such as battery charge running out and is wants to incentive the direct user intervention.
-    ///     Visualizations should emphasize in some way the items with `NeedsAttention` status.
+    ///   Visualizations should emphasize in some way the items with `NeedsAttention` status.
     pub status: Status,
 
     /// The windowing-system dependent identifier for a window, the application can choose one of its windows to be available through this property or just set 0 if it's not interested.
@@ -108,7 +112,7 @@ impl From<&str> for Category {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, Deserialize, Default, PartialEq, Eq)]
 pub enum Status {
     #[default]
     Unknown,
@@ -128,7 +132,7 @@ impl From<&str> for Status {
     }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, PartialEq, Eq)]
 pub struct IconPixmap {
     pub width: i32,
     pub height: i32,
@@ -146,6 +150,146 @@ impl Debug for IconPixmap {
 }
 
 impl IconPixmap {
+    /// Picks the pixmap best suited to being shown at `size` pixels,
+    /// which is the smallest one at least that large, or else the largest one.
+    #[must_use]
+    pub fn for_size(pixmaps: &[Self], size: i32) -> Option<&Self> {
+        let area = |pixmap: &&Self| i64::from(pixmap.width) * i64::from(pixmap.height);
+
+        pixmaps
+            .iter()
+            .filter(|pixmap| pixmap.width >= size && pixmap.height >= size)
+            .min_by_key(area)
+            .or_else(|| pixmaps.iter().max_by_key(area))
+    }
+
+    /// Recolors the pixmap with the RGB `color`, keeping its alpha channel,
+    /// as done for symbolic icons so they match the surrounding text.
+    #[must_use]
+    pub fn tinted(&self, color: [u8; 3]) -> Self {
+        let mut pixmap = self.clone();
+        for pixel in pixmap.pixels.chunks_exact_mut(4) {
+            pixel[1..].copy_from_slice(&color);
+        }
+        pixmap
+    }
+
+    /// Scales the pixmap down to fit within `size` pixels square,
+    /// keeping its aspect ratio.
+    ///
+    /// Pixmaps which already fit, or have too few pixels for their size,
+    /// are returned as they are.
+    #[must_use]
+    pub fn scaled(&self, size: i32, quality: ScaleQuality) -> Self {
+        let (Ok(width), Ok(height), Ok(size)) = (
+            usize::try_from(self.width),
+            usize::try_from(self.height),
+            usize::try_from(size),
+        ) else {
+            return self.clone();
+        };
+
+        let longest = width.max(height);
+        if size == 0 || longest <= size || self.pixels.len() < width * height * 4 {
+            return self.clone();
+        }
+
+        let new_width = (width * size / longest).max(1);
+        let new_height = (height * size / longest).max(1);
+
+        let mut pixels = Vec::with_capacity(new_width * new_height * 4);
+        for y in 0..new_height {
+            let (top, bottom) = (y * height / new_height, (y + 1) * height / new_height);
+
+            for x in 0..new_width {
+                let (left, right) = (x * width / new_width, (x + 1) * width / new_width);
+
+                match quality {
+                    ScaleQuality::Fast => {
+                        let start = (top * width + left) * 4;
+                        pixels.extend_from_slice(&self.pixels[start..start + 4]);
+                    }
+                    ScaleQuality::Smooth => {
+                        // channels are weighted by alpha,
+                        // so transparent pixels do not darken the edges
+                        let mut sums = [0u64; 4];
+                        let mut count = 0;
+                        for row in top..bottom.max(top + 1) {
+                            for column in left..right.max(left + 1) {
+                                let start = (row * width + column) * 4;
+                                let pixel = &self.pixels[start..start + 4];
+                                let alpha = u64::from(pixel[0]);
+
+                                sums[0] += alpha;
+                                for channel in 1..4 {
+                                    sums[channel] += u64::from(pixel[channel]) * alpha;
+                                }
+                                count += 1;
+                            }
+                        }
+
+                        let alpha = sums[0] / count;
+                        pixels.push(u8::try_from(alpha).unwrap_or(u8::MAX));
+                        for sum in &sums[1..] {
+                            let value = sum.checked_div(sums[0]).unwrap_or(0);
+                            pixels.push(u8::try_from(value).unwrap_or(u8::MAX));
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            width: i32::try_from(new_width).unwrap_or(i32::MAX),
+            height: i32::try_from(new_height).unwrap_or(i32::MAX),
+            pixels,
+        }
+    }
+
+    /// Reverses the order of the bytes in each pixel,
+    /// converting between ARGB32 and BGRA32.
+    pub(crate) fn swap_byte_order(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.reverse();
+        }
+    }
+
+    /// Checks whether the pixels look like they were sent in little-endian byte order.
+    ///
+    /// Fully transparent pixels almost always have their color channels cleared too.
+    /// In network byte order the alpha channel is the first byte of each pixel,
+    /// so if many pixels have a zero first byte but some color,
+    /// while few do when reading the last byte as alpha instead,
+    /// the order is assumed to be wrong.
+    pub(crate) fn looks_byte_swapped(&self) -> bool {
+        let colored_transparent = |alpha: usize| {
+            self.pixels
+                .chunks_exact(4)
+                .filter(|pixel| pixel[alpha] == 0 && pixel.iter().any(|&byte| byte != 0))
+                .count()
+        };
+
+        let network = colored_transparent(0);
+        let swapped = colored_transparent(3);
+        let pixel_count = self.pixels.len() / 4;
+
+        network > 0 && network >= pixel_count / 8 && swapped * 2 < network
+    }
+
+    /// Fixes the byte order of `pixmaps` sent by applications using the wrong one.
+    ///
+    /// If `swap` is set, it decides whether the bytes are swapped,
+    /// otherwise they are swapped only if [`IconPixmap::looks_byte_swapped`].
+    pub(crate) fn fix_byte_order(pixmaps: &mut [Self], swap: Option<bool>) {
+        let swap = swap.unwrap_or_else(|| pixmaps.iter().any(Self::looks_byte_swapped));
+
+        if swap {
+            for pixmap in pixmaps {
+                pixmap.swap_byte_order();
+            }
+        }
+    }
+
     fn from_array(array: &Array) -> Result<Vec<Self>> {
         array
             .iter()
@@ -159,8 +303,8 @@ impl IconPixmap {
                     .downcast_ref::<i32>()?;
 
                 let height = fields
-                    .first()
-                    .ok_or(Error::InvalidData("invalid or missing width"))?
+                    .get(1)
+                    .ok_or(Error::InvalidData("invalid or missing height"))?
                     .downcast_ref::<i32>()?;
 
                 let pixel_values = fields
@@ -185,7 +329,7 @@ impl IconPixmap {
 
 /// Data structure that describes extra information associated to this item, that can be visualized for instance by a tooltip
 /// (or by any other mean the visualization consider appropriate.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct Tooltip {
     pub icon_name: String,
     pub icon_data: Vec<IconPixmap>,
@@ -193,6 +337,14 @@ pub struct Tooltip {
     pub description: String,
 }
 
+impl Tooltip {
+    /// Sanitizes the title and description of the tooltip, see [`sanitize_text`].
+    pub(crate) fn sanitize(&mut self, max_length: usize) {
+        sanitize_text(&mut self.title, max_length);
+        sanitize_text(&mut self.description, max_length);
+    }
+}
+
 impl TryFrom<&Structure<'_>> for Tooltip {
     type Error = Error;
 
@@ -232,58 +384,399 @@ impl TryFrom<DBusProps> for StatusNotifierItem {
     type Error = Error;
 
     fn try_from(props: DBusProps) -> Result<Self> {
-        if let Some(id) = props.get_string("Id") {
-            let id = id?;
-            Ok(Self {
-                id,
-                title: props.get_string("Title").transpose()?,
-                status: props.get_status()?,
-                window_id: props
-                    .get::<i32>("WindowId")
-                    .transpose()?
-                    .copied()
-                    .unwrap_or_default() as u32,
-                icon_theme_path: props.get_string("IconThemePath").transpose()?,
-                icon_name: props.get_string("IconName").transpose()?,
-                icon_pixmap: props.get_icon_pixmap("IconPixmap").transpose()?,
-                overlay_icon_name: props.get_string("OverlayIconName").transpose()?,
-                overlay_icon_pixmap: props.get_icon_pixmap("OverlayIconPixmap").transpose()?,
-                attention_icon_name: props.get_string("AttentionIconName").transpose()?,
-                attention_icon_pixmap: props.get_icon_pixmap("AttentionIconPixmap").transpose()?,
-                attention_movie_name: props.get_string("AttentionMovieName").transpose()?,
-                tool_tip: props.get_tooltip().transpose()?,
-                item_is_menu: props
-                    .get("ItemIsMenu")
-                    .transpose()?
-                    .copied()
-                    .unwrap_or_default(),
-                category: props.get_category()?,
-                menu: props.get_object_path("Menu").transpose()?,
-            })
-        } else {
-            Err(Error::MissingProperty("Id"))
+        Self::from_props(&props).map(|(item, _)| item)
+    }
+}
+
+impl StatusNotifierItem {
+    /// Parses an item from its properties.
+    ///
+    /// Only the `Id` property is required.
+    /// Any other property which does not have the expected type is logged and skipped,
+    /// so that one misbehaving property does not discard the rest of the item.
+    /// The names of skipped properties are returned alongside the item, with the reason.
+    pub(crate) fn from_props(props: &DBusProps) -> Result<(Self, Vec<(&'static str, Error)>)> {
+        let id = props
+            .get_string("Id")
+            .ok_or(Error::MissingProperty("Id"))??;
+
+        let mut skipped = vec![];
+        macro_rules! lenient {
+            ($name:literal, $value:expr $(,)?) => {
+                skip_invalid(&id, $name, $value, &mut skipped)
+            };
+        }
+
+        let item = Self {
+            title: lenient!("Title", props.get_string("Title")),
+            status: lenient!("Status", props.get_status()).unwrap_or_default(),
+            window_id: lenient!("WindowId", props.get::<i32>("WindowId"))
+                .copied()
+                .unwrap_or_default() as u32,
+            icon_theme_path: lenient!("IconThemePath", props.get_string("IconThemePath")),
+            icon_name: lenient!("IconName", props.get_string("IconName")),
+            icon_pixmap: lenient!("IconPixmap", props.get_icon_pixmap("IconPixmap")),
+            overlay_icon_name: lenient!("OverlayIconName", props.get_string("OverlayIconName")),
+            overlay_icon_pixmap: lenient!(
+                "OverlayIconPixmap",
+                props.get_icon_pixmap("OverlayIconPixmap"),
+            ),
+            attention_icon_name: lenient!(
+                "AttentionIconName",
+                props.get_string("AttentionIconName"),
+            ),
+            attention_icon_pixmap: lenient!(
+                "AttentionIconPixmap",
+                props.get_icon_pixmap("AttentionIconPixmap"),
+            ),
+            attention_movie_name: lenient!(
+                "AttentionMovieName",
+                props.get_string("AttentionMovieName"),
+            ),
+            tool_tip: lenient!("ToolTip", props.get_tooltip()),
+            item_is_menu: lenient!("ItemIsMenu", props.get::<bool>("ItemIsMenu"))
+                .copied()
+                .unwrap_or_default(),
+            category: lenient!("Category", props.get_category()).unwrap_or_default(),
+            menu: lenient!("Menu", props.get_object_path("Menu")),
+            id,
+        };
+
+        Ok((item, skipped))
+    }
+
+    /// Fixes the byte order of all pixmaps on the item.
+    ///
+    /// See [`IconPixmap::fix_byte_order`].
+    pub(crate) fn fix_pixmap_byte_order(&mut self, swap: Option<bool>) {
+        let pixmaps = [
+            self.icon_pixmap.as_mut(),
+            self.overlay_icon_pixmap.as_mut(),
+            self.attention_icon_pixmap.as_mut(),
+            self.tool_tip
+                .as_mut()
+                .map(|tool_tip| &mut tool_tip.icon_data),
+        ];
+
+        for pixmaps in pixmaps.into_iter().flatten() {
+            IconPixmap::fix_byte_order(pixmaps, swap);
         }
     }
+
+    /// Sanitizes the title and tooltip of the item, see [`sanitize_text`].
+    pub(crate) fn sanitize(&mut self, max_length: usize) {
+        if let Some(title) = &mut self.title {
+            sanitize_text(title, max_length);
+        }
+        if let Some(tool_tip) = &mut self.tool_tip {
+            tool_tip.sanitize(max_length);
+        }
+    }
+
+    /// Gets the main icon of the item.
+    ///
+    /// The icon name is preferred over the pixmap, as recommended by the specification.
+    #[must_use]
+    pub fn icon(&self) -> Icon {
+        Icon::new(self.icon_name.as_deref(), self.icon_pixmap.as_deref())
+    }
+
+    /// Gets the icon to show when the item is requesting attention.
+    ///
+    /// The icon name is preferred over the pixmap, as recommended by the specification.
+    /// This does not fall back to the main icon, or consider `attention_movie_name`.
+    #[must_use]
+    pub fn attention_icon(&self) -> Icon {
+        Icon::new(
+            self.attention_icon_name.as_deref(),
+            self.attention_icon_pixmap.as_deref(),
+        )
+    }
+
+    /// Gets the icon to draw over the main icon.
+    ///
+    /// The icon name is preferred over the pixmap, as recommended by the specification.
+    #[must_use]
+    pub fn overlay_icon(&self) -> Icon {
+        Icon::new(
+            self.overlay_icon_name.as_deref(),
+            self.overlay_icon_pixmap.as_deref(),
+        )
+    }
+}
+
+/// An icon of an item,
+/// taken from whichever of its name or pixmap properties is set.
+#[derive(Debug, Clone)]
+pub enum Icon {
+    /// A Freedesktop-compliant icon name, or a full path to an icon.
+    Named(String),
+    /// ARGB32 icon data, possibly at several sizes.
+    Pixmaps(Vec<IconPixmap>),
+    /// PNG-encoded icon data, as sent for menu items.
+    Png(Vec<u8>),
+    /// Neither property is set.
+    None,
+}
+
+impl Icon {
+    /// Picks the icon from its `name` and `pixmaps` properties,
+    /// preferring the name.
+    ///
+    /// Empty values are treated as unset,
+    /// as many applications set an empty name when only providing pixmaps.
+    fn new(name: Option<&str>, pixmaps: Option<&[IconPixmap]>) -> Self {
+        match (name, pixmaps) {
+            (Some(name), _) if !name.is_empty() => Self::Named(name.to_string()),
+            (_, Some(pixmaps)) if !pixmaps.is_empty() => Self::Pixmaps(pixmaps.to_vec()),
+            _ => Self::None,
+        }
+    }
+
+    /// Checks whether the icon is symbolic,
+    /// meaning it is monochrome and meant to be recolored to match the surrounding text.
+    ///
+    /// Following the icon naming specification,
+    /// symbolic icons have names ending in `-symbolic`.
+    #[must_use]
+    pub fn is_symbolic(&self) -> bool {
+        match self {
+            Self::Named(name) => Path::new(name)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| stem.ends_with("-symbolic")),
+            _ => false,
+        }
+    }
+
+    /// Recolors the icon with the RGB `color`, if it is a pixmap.
+    ///
+    /// Named icons are returned as they are,
+    /// as they have to be recolored once loaded from the theme.
+    #[must_use]
+    pub fn tinted(&self, color: [u8; 3]) -> Self {
+        match self {
+            Self::Pixmaps(pixmaps) => {
+                Self::Pixmaps(pixmaps.iter().map(|pixmap| pixmap.tinted(color)).collect())
+            }
+            icon => icon.clone(),
+        }
+    }
+
+    /// Gets the source of the icon without decoding it,
+    /// for consumers which can pass it on as it is.
+    ///
+    /// Names which are absolute paths are returned as files.
+    /// For pixmaps, only the largest size is returned.
+    ///
+    /// Returns `None` if no icon is set.
+    #[must_use]
+    pub fn raw(&self) -> Option<IconSource<'_>> {
+        match self {
+            Self::Named(name) if Path::new(name).is_absolute() => {
+                Some(IconSource::File(PathBuf::from(name)))
+            }
+            Self::Named(name) => Some(IconSource::ThemeName(name)),
+            Self::Pixmaps(pixmaps) => pixmaps
+                .iter()
+                .max_by_key(|pixmap| i64::from(pixmap.width) * i64::from(pixmap.height))
+                .map(|pixmap| IconSource::Pixmap {
+                    width: pixmap.width,
+                    height: pixmap.height,
+                    pixels: &pixmap.pixels,
+                }),
+            Self::Png(data) => Some(IconSource::Png(data)),
+            Self::None => None,
+        }
+    }
+}
+
+/// How pixmaps are resampled when scaled down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ScaleQuality {
+    /// Nearest-neighbour sampling, which is quick but jagged.
+    Fast,
+    /// Averages the pixels covered by each new pixel.
+    #[default]
+    Smooth,
+}
+
+/// The number of scaled pixmaps kept by a [`PixmapCache`]
+/// before the least recently used one is dropped.
+const PIXMAP_CACHE_CAPACITY: usize = 64;
+
+/// Which of the icons of an item a pixmap belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IconKind {
+    /// The main icon, from `icon_pixmap`.
+    Main,
+    /// The overlay icon, from `overlay_icon_pixmap`.
+    Overlay,
+    /// The icon shown when requesting attention, from `attention_icon_pixmap`.
+    Attention,
+}
+
+impl IconKind {
+    /// Gets the pixmaps of this icon of `item`.
+    fn pixmaps(self, item: &StatusNotifierItem) -> Option<&[IconPixmap]> {
+        match self {
+            Self::Main => item.icon_pixmap.as_deref(),
+            Self::Overlay => item.overlay_icon_pixmap.as_deref(),
+            Self::Attention => item.attention_icon_pixmap.as_deref(),
+        }
+    }
+
+    /// Gets the icon whose pixmap `update` changes, if any.
+    fn for_update(update: &UpdateEvent) -> Option<Self> {
+        match update {
+            UpdateEvent::IconPixmap(_) => Some(Self::Main),
+            UpdateEvent::OverlayIconPixmap(_) => Some(Self::Overlay),
+            UpdateEvent::AttentionIconPixmap(_) => Some(Self::Attention),
+            _ => None,
+        }
+    }
+}
+
+/// A cache of pixmaps scaled down to the sizes they are shown at,
+/// so large icons are only resampled once.
+///
+/// Entries are keyed on the item address, so the cache must be passed
+/// each event from the client with [`PixmapCache::handle_event`]
+/// for a changed icon to be scaled again.
+#[derive(Debug, Clone, Default)]
+pub struct PixmapCache {
+    quality: ScaleQuality,
+    entries: HashMap<(String, IconKind, i32), PixmapCacheEntry>,
+    /// Counts up on each lookup, to find the least recently used entry.
+    uses: u64,
+}
+
+#[derive(Debug, Clone)]
+struct PixmapCacheEntry {
+    pixmap: IconPixmap,
+    last_used: u64,
+}
+
+impl PixmapCache {
+    /// Creates an empty cache, which scales with `quality`.
+    #[must_use]
+    pub fn new(quality: ScaleQuality) -> Self {
+        Self {
+            quality,
+            entries: HashMap::new(),
+            uses: 0,
+        }
+    }
+
+    /// Gets the pixmap to show at `size` pixels for the `kind` icon of `item`,
+    /// picking the best of its pixmaps and scaling it down if it is too large.
+    ///
+    /// Returns `None` if the item has no pixmaps for the icon.
+    pub fn get(
+        &mut self,
+        address: &str,
+        item: &StatusNotifierItem,
+        kind: IconKind,
+        size: i32,
+    ) -> Option<&IconPixmap> {
+        let pixmap = IconPixmap::for_size(kind.pixmaps(item)?, size)?;
+        let key = (address.to_string(), kind, size);
+
+        if !self.entries.contains_key(&key) && self.entries.len() >= PIXMAP_CACHE_CAPACITY {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.uses += 1;
+        let entry = self.entries.entry(key).or_insert_with(|| PixmapCacheEntry {
+            pixmap: pixmap.scaled(size, self.quality),
+            last_used: 0,
+        });
+        entry.last_used = self.uses;
+
+        Some(&entry.pixmap)
+    }
+
+    /// Drops the scaled pixmaps which `event` makes out of date.
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::Add(address, _) | Event::Remove(address) => {
+                self.entries
+                    .retain(|(entry_address, _, _), _| entry_address != address);
+            }
+            Event::Update(address, update) => {
+                if let Some(kind) = IconKind::for_update(update) {
+                    self.entries.retain(|(entry_address, entry_kind, _), _| {
+                        entry_address != address || *entry_kind != kind
+                    });
+                }
+            }
+            Event::Reset(_) | Event::Reconnected(_) => self.clear(),
+            Event::Warning(..) => {}
+        }
+    }
+
+    /// Removes all scaled pixmaps.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// The undecoded source of an [`Icon`], borrowed from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconSource<'a> {
+    /// An icon name, to look up in the icon theme.
+    ThemeName(&'a str),
+    /// ARGB32 pixels in network byte order.
+    Pixmap {
+        width: i32,
+        height: i32,
+        pixels: &'a [u8],
+    },
+    /// The path to an icon file.
+    File(PathBuf),
+    /// PNG-encoded icon data.
+    Png(&'a [u8]),
+}
+
+/// Unwraps the value of an optional property,
+/// logging and recording it as skipped if it could not be parsed.
+fn skip_invalid<T>(
+    id: &str,
+    name: &'static str,
+    value: Option<Result<T>>,
+    skipped: &mut Vec<(&'static str, Error)>,
+) -> Option<T> {
+    match value {
+        Some(Ok(value)) => Some(value),
+        Some(Err(err)) => {
+            warn!("[{id}] skipping invalid property {name}: {err}");
+            skipped.push((name, err));
+            None
+        }
+        None => None,
+    }
 }
 
 impl DBusProps {
-    fn get_category(&self) -> Result<Category> {
-        Ok(self
-            .get::<str>("Category")
-            .transpose()?
-            .map(Category::from)
-            .unwrap_or_default())
+    fn get_category(&self) -> Option<Result<Category>> {
+        self.get::<str>("Category")
+            .map(|category| category.map(Category::from))
     }
 
-    fn get_status(&self) -> Result<Status> {
-        Ok(self
-            .get::<str>("Status")
-            .transpose()?
-            .map(Status::from)
-            .unwrap_or_default())
+    fn get_status(&self) -> Option<Result<Status>> {
+        self.get::<str>("Status")
+            .map(|status| status.map(Status::from))
     }
 
-    fn get_icon_pixmap(&self, key: &str) -> Option<Result<Vec<IconPixmap>>> {
+    pub(crate) fn get_icon_pixmap(&self, key: &str) -> Option<Result<Vec<IconPixmap>>> {
         self.get::<Array>(key)
             .map(|arr| arr.and_then(IconPixmap::from_array))
     }
@@ -293,3 +786,243 @@ impl DBusProps {
             .map(|t| t.and_then(Tooltip::try_from))
     }
 }
+
+/// Removes control characters from `text`, apart from line breaks and tabs,
+/// and cuts it to at most `max_length` characters.
+///
+/// Text from applications is shown, and often logged, as-is,
+/// so this stops a broken or malicious one injecting terminal escape sequences
+/// or huge labels.
+/// Invalid UTF-8 never gets this far, as messages containing it are rejected by zbus.
+pub(crate) fn sanitize_text(text: &mut String, max_length: usize) {
+    let is_allowed = |char: &char| !char.is_control() || matches!(char, '\n' | '\t');
+
+    if text.len() <= max_length && text.chars().all(|char| is_allowed(&char)) {
+        return;
+    }
+
+    *text = text.chars().filter(is_allowed).take(max_length).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use zbus::zvariant::{OwnedValue, Str, Value};
+
+    fn make_props(values: Vec<(&str, OwnedValue)>) -> DBusProps {
+        DBusProps(
+            values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn mistyped_properties_are_skipped() {
+        let props = make_props(vec![
+            ("Id", OwnedValue::from(Str::from("app"))),
+            ("Title", OwnedValue::from(Str::from("App"))),
+            ("IconName", OwnedValue::from(42u32)),
+            ("Status", OwnedValue::from(1i32)),
+            ("ItemIsMenu", OwnedValue::from(true)),
+        ]);
+
+        let (item, skipped) = StatusNotifierItem::from_props(&props).expect("item should parse");
+
+        assert_eq!("app", item.id);
+        assert_eq!(Some("App"), item.title.as_deref());
+        assert_eq!(None, item.icon_name);
+        assert!(matches!(item.status, Status::Unknown));
+        assert!(item.item_is_menu);
+
+        let mut names = skipped.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(vec!["IconName", "Status"], names);
+    }
+
+    #[test]
+    fn mistyped_id_is_error() {
+        let props = make_props(vec![("Id", OwnedValue::from(42u32))]);
+        assert!(StatusNotifierItem::from_props(&props).is_err());
+
+        let props = make_props(vec![]);
+        assert!(matches!(
+            StatusNotifierItem::from_props(&props),
+            Err(Error::MissingProperty("Id"))
+        ));
+    }
+
+    #[test]
+    fn icon_precedence() {
+        let pixmaps = [IconPixmap {
+            width: 1,
+            height: 1,
+            pixels: vec![0; 4],
+        }];
+
+        assert!(matches!(
+            Icon::new(Some("name"), Some(&pixmaps)),
+            Icon::Named(name) if name == "name"
+        ));
+        assert!(matches!(
+            Icon::new(Some(""), Some(&pixmaps)),
+            Icon::Pixmaps(pixmaps) if pixmaps.len() == 1
+        ));
+        assert!(matches!(Icon::new(Some(""), Some(&[])), Icon::None));
+        assert!(matches!(Icon::new(None, None), Icon::None));
+    }
+
+    #[test]
+    fn icon_raw() {
+        let icon = Icon::Named("/usr/share/icons/app.png".to_string());
+        assert_eq!(
+            Some(IconSource::File(PathBuf::from("/usr/share/icons/app.png"))),
+            icon.raw()
+        );
+
+        let icon = Icon::Named("app".to_string());
+        assert_eq!(Some(IconSource::ThemeName("app")), icon.raw());
+
+        let icon = Icon::Pixmaps(vec![
+            IconPixmap {
+                width: 1,
+                height: 1,
+                pixels: vec![0; 4],
+            },
+            IconPixmap {
+                width: 2,
+                height: 2,
+                pixels: vec![1; 16],
+            },
+        ]);
+        assert!(matches!(
+            icon.raw(),
+            Some(IconSource::Pixmap { width: 2, height: 2, pixels }) if pixels.len() == 16
+        ));
+
+        assert_eq!(None, Icon::None.raw());
+    }
+
+    #[test]
+    fn symbolic_icon() {
+        assert!(Icon::Named("network-wireless-symbolic".to_string()).is_symbolic());
+        assert!(Icon::Named("/opt/app/mute-symbolic.svg".to_string()).is_symbolic());
+        assert!(!Icon::Named("network-wireless".to_string()).is_symbolic());
+
+        let icon = Icon::Pixmaps(vec![IconPixmap {
+            width: 2,
+            height: 1,
+            pixels: vec![0xff, 0x00, 0x00, 0x00, 0x40, 0x12, 0x34, 0x56],
+        }]);
+        let Icon::Pixmaps(pixmaps) = icon.tinted([0xaa, 0xbb, 0xcc]) else {
+            panic!("pixmaps should stay pixmaps");
+        };
+        assert_eq!(
+            vec![0xff, 0xaa, 0xbb, 0xcc, 0x40, 0xaa, 0xbb, 0xcc],
+            pixmaps[0].pixels
+        );
+    }
+
+    #[test]
+    fn downscale() {
+        // 2x2 with one opaque white pixel
+        let mut pixels = vec![0; 16];
+        pixels[..4].copy_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        let large = IconPixmap {
+            width: 2,
+            height: 2,
+            pixels,
+        };
+        let small = IconPixmap {
+            width: 1,
+            height: 1,
+            pixels: vec![0xff, 0, 0, 0],
+        };
+        let pixmaps = vec![small.clone(), large.clone()];
+
+        assert_eq!(Some(&pixmaps[0]), IconPixmap::for_size(&pixmaps, 1));
+        assert_eq!(Some(&pixmaps[1]), IconPixmap::for_size(&pixmaps, 4));
+
+        let fast = large.scaled(1, ScaleQuality::Fast);
+        assert_eq!((1, 1), (fast.width, fast.height));
+        assert_eq!(vec![0xff, 0xff, 0xff, 0xff], fast.pixels);
+
+        let smooth = large.scaled(1, ScaleQuality::Smooth);
+        assert_eq!(vec![0x3f, 0xff, 0xff, 0xff], smooth.pixels);
+
+        assert_eq!(large, large.scaled(2, ScaleQuality::Smooth));
+
+        let props = make_props(vec![("Id", OwnedValue::from(Str::from("app")))]);
+        let mut item = StatusNotifierItem::try_from(props).expect("item should parse");
+        item.icon_pixmap = Some(vec![large]);
+
+        let mut cache = PixmapCache::new(ScaleQuality::Smooth);
+        let cached = cache
+            .get(":1.1", &item, IconKind::Main, 1)
+            .expect("pixmap should be scaled");
+        assert_eq!(smooth, *cached);
+        assert!(cache.get(":1.1", &item, IconKind::Overlay, 1).is_none());
+
+        // the cached pixmap is kept until the client announces a new one
+        item.icon_pixmap = Some(vec![small.clone()]);
+        assert_eq!(Some(&smooth), cache.get(":1.1", &item, IconKind::Main, 1));
+
+        cache.handle_event(&Event::Update(
+            ":1.1".to_string(),
+            UpdateEvent::IconPixmap(item.icon_pixmap.clone()),
+        ));
+        assert_eq!(Some(&small), cache.get(":1.1", &item, IconKind::Main, 1));
+    }
+
+    #[test]
+    fn pixmap_size() {
+        let pixmap = Value::from((2i32, 1i32, vec![0u8; 8]));
+        let array = Array::from(vec![pixmap]);
+
+        let pixmaps = IconPixmap::from_array(&array).expect("pixmap should parse");
+        assert_eq!(2, pixmaps[0].width);
+        assert_eq!(1, pixmaps[0].height);
+    }
+
+    #[test]
+    fn pixmap_byte_order() {
+        let mut pixmaps = vec![IconPixmap {
+            width: 2,
+            height: 1,
+            pixels: vec![0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00],
+        }];
+
+        IconPixmap::fix_byte_order(&mut pixmaps, None);
+        assert_eq!(
+            vec![0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            pixmaps[0].pixels
+        );
+
+        // correctly ordered pixmaps are left alone
+        IconPixmap::fix_byte_order(&mut pixmaps, None);
+        assert_eq!(0xff, pixmaps[0].pixels[0]);
+
+        IconPixmap::fix_byte_order(&mut pixmaps, Some(false));
+        assert_eq!(0xff, pixmaps[0].pixels[0]);
+
+        IconPixmap::fix_byte_order(&mut pixmaps, Some(true));
+        assert_eq!(0x00, pixmaps[0].pixels[0]);
+    }
+
+    #[test]
+    fn sanitize() {
+        let mut text = "\x1b[31mred\x07 text\n".to_string();
+        sanitize_text(&mut text, 100);
+        assert_eq!("[31mred text\n", text);
+
+        let mut text = "ééééé".to_string();
+        sanitize_text(&mut text, 3);
+        assert_eq!("ééé", text);
+
+        let mut text = "plain".to_string();
+        sanitize_text(&mut text, 5);
+        assert_eq!("plain", text);
+    }
+}
@@ -29,18 +29,36 @@ mod dbus;
 /// and associated types.
 pub mod client;
 
+/// Sources of time for the client's delays and periodic checks.
+pub mod clock;
+
+/// Keyboard navigation through menus.
+pub mod cursor;
+
 /// Error and result types.
 pub mod error;
 
+/// Recording and replaying the messages received by the client.
+pub mod fixture;
+
 /// `StatusNotifierItem` item representation.
 pub mod item;
 
 /// `DBusMenu` menu representation.
 pub mod menu;
 
+/// Tray state maintained from client events.
+pub mod state;
+
+/// Scripted items on a private bus, for testing tray hosts without a desktop session.
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
 #[cfg(feature = "dbusmenu-gtk3")]
 pub mod gtk_menu;
 
+pub(crate) mod sync;
+
 pub(crate) mod names {
     pub const WATCHER_BUS: &str = "org.kde.StatusNotifierWatcher";
     pub const WATCHER_OBJECT: &str = "/StatusNotifierWatcher";
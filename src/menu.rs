@@ -1,7 +1,12 @@
+#[cfg(test)]
+use crate::dbus::dbus_menu_proxy::RemovedProps;
 use crate::dbus::dbus_menu_proxy::{MenuLayout, PropertiesUpdate, UpdatedProps};
+use crate::dbus::dict_get;
 use crate::error::{Error, Result};
+use crate::item::{sanitize_text, Icon};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use zbus::zvariant::{Array, OwnedValue, Structure, Value};
 
 /// A menu that should be displayed when clicking corresponding tray icon
@@ -9,27 +14,153 @@ use zbus::zvariant::{Array, OwnedValue, Structure, Value};
 pub struct TrayMenu {
     /// The unique identifier of the menu
     pub id: u32,
+    /// The version of the dbusmenu protocol implemented by the application,
+    /// if it reported one.
+    ///
+    /// This indicates which optional methods are safe to call on the menu.
+    pub version: Option<u32>,
+    /// Extra directories to search for icons named by the menu items,
+    /// for applications which ship their own icons.
+    pub icon_theme_path: Vec<String>,
+    /// Whether the menu needs the user's attention.
+    pub status: MenuStatus,
+    /// The direction of the text in the menu,
+    /// which decides the side submenus open on.
+    pub text_direction: TextDirection,
     /// A recursive list of submenus
     pub submenus: Vec<MenuItem>,
 }
 
+impl TrayMenu {
+    /// Finds the item with `id` at any level of the menu.
+    #[must_use]
+    pub fn find_item(&self, id: i32) -> Option<&MenuItem> {
+        find_item(&self.submenus, id)
+    }
+
+    /// Applies property changes sent by the application to the items of the menu.
+    ///
+    /// Updated properties are set, and removed properties go back to their defaults.
+    /// Changes for items not in the menu are skipped.
+    pub fn apply_diffs(&mut self, diffs: &[MenuDiff]) {
+        for diff in diffs {
+            if let Some(item) = self.find_item_mut(diff.id) {
+                item.apply_diff(diff);
+            }
+        }
+    }
+
+    /// Finds the item with `id` at any level of the menu, for changing it.
+    pub fn find_item_mut(&mut self, id: i32) -> Option<&mut MenuItem> {
+        find_item_mut(&mut self.submenus, id)
+    }
+
+    /// Removes the items which are not visible, at every level of the menu.
+    ///
+    /// Separators which would end up at the start or end of a menu,
+    /// or next to another separator, are removed too.
+    pub fn retain_visible(&mut self) {
+        retain_visible(&mut self.submenus);
+    }
+}
+
+fn find_item(items: &[MenuItem], id: i32) -> Option<&MenuItem> {
+    items.iter().find_map(|item| {
+        if item.id == id {
+            Some(item)
+        } else {
+            find_item(&item.submenu, id)
+        }
+    })
+}
+
+fn find_item_mut(items: &mut [MenuItem], id: i32) -> Option<&mut MenuItem> {
+    items.iter_mut().find_map(|item| {
+        if item.id == id {
+            Some(item)
+        } else {
+            find_item_mut(&mut item.submenu, id)
+        }
+    })
+}
+
+fn retain_visible(items: &mut Vec<MenuItem>) {
+    items.retain(|item| item.visible);
+
+    let mut previous_separator = true;
+    items.retain(|item| {
+        let keep = !(item.is_separator() && previous_separator);
+        previous_separator = item.is_separator();
+        keep
+    });
+
+    if items.last().is_some_and(MenuItem::is_separator) {
+        items.pop();
+    }
+
+    for item in items {
+        retain_visible(&mut item.submenu);
+    }
+}
+
+/// Whether a menu needs the user's attention.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum MenuStatus {
+    /// The menu should be shown as usual.
+    #[default]
+    Normal,
+    /// The menu has something important to show, and should be highlighted.
+    Notice,
+}
+
+impl From<&str> for MenuStatus {
+    fn from(value: &str) -> Self {
+        match value {
+            "notice" => Self::Notice,
+            _ => Self::default(),
+        }
+    }
+}
+
+/// The direction of the text in a menu.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum TextDirection {
+    /// Left to right.
+    #[default]
+    LeftToRight,
+    /// Right to left, for which the menu should be mirrored.
+    RightToLeft,
+}
+
+impl From<&str> for TextDirection {
+    fn from(value: &str) -> Self {
+        match value {
+            "rtl" => Self::RightToLeft,
+            _ => Self::default(),
+        }
+    }
+}
+
 /// List of properties taken from:
 /// <https://github.com/AyatanaIndicators/libdbusmenu/blob/4d03141aea4e2ad0f04ab73cf1d4f4bcc4a19f6c/libdbusmenu-glib/dbus-menu.xml#L75>
-#[derive(Debug, Clone, Deserialize, Default)]
+///
+/// Properties the application does not send take the default values from the spec,
+/// which are those of [`MenuItem::default`].
+#[derive(Debug, Clone, Deserialize)]
 pub struct MenuItem {
     /// Unique numeric id
     pub id: i32,
 
     /// Either a standard menu item or a separator [`MenuType`]
     pub menu_type: MenuType,
-    /// Text of the item, except that:
-    ///  - two consecutive underscore characters "__" are displayed as a
-    ///    single underscore,
-    ///  - any remaining underscore characters are not displayed at all,
-    ///  - the first of those remaining underscore characters (unless it is
-    ///    the last character in the string) indicates that the following
-    ///    character is the access key.
+    /// Text of the item, as it should be displayed.
+    ///
+    /// The application marks the access key with an underscore
+    /// and escapes literal underscores as "__".
+    /// Both are processed here, with the access key in [`MenuItem::mnemonic`].
     pub label: Option<String>,
+    /// The access key of the item, marked in its label.
+    pub mnemonic: Option<char>,
     /// Whether the item can be activated or not.
     pub enabled: bool,
     /// True if the item is visible in the menu.
@@ -38,16 +169,13 @@ pub struct MenuItem {
     pub icon_name: Option<String>,
     /// PNG data of the icon.
     pub icon_data: Option<Vec<u8>>,
-    /// The shortcut of the item. Each array represents the key press
-    /// in the list of keypresses. Each list of strings contains a list of
-    /// modifiers and then the key that is used. The modifier strings
-    /// allowed are: "Control", "Alt", "Shift" and "Super".
+    /// The shortcut of the item, as the sequence of key presses which triggers it.
+    ///
+    /// - A simple shortcut like Ctrl+S is a single [`Shortcut`].
+    /// - A complex shortcut like Ctrl+Q, Alt+X is two.
     ///
-    /// - A simple shortcut like Ctrl+S is represented as:
-    ///   [["Control", "S"]]
-    /// - A complex shortcut like Ctrl+Q, Alt+X is represented as:
-    ///   [["Control", "Q"], ["Alt", "X"]]
-    pub shortcut: Option<Vec<Vec<String>>>,
+    /// See [`MenuItem::shortcut_label`] for displaying it.
+    pub shortcut: Option<Vec<Shortcut>>,
     /// How the menuitem feels the information it's displaying to the
     /// user should be presented.
     /// See [`ToggleType`].
@@ -63,6 +191,8 @@ pub struct MenuItem {
     pub toggle_state: ToggleState,
     /// If the menu item has children this property should be set to
     /// "submenu".
+    ///
+    /// See [`MenuItem::has_submenu`].
     pub children_display: Option<String>,
     /// How the menuitem feels the information it's displaying to the
     /// user should be presented.
@@ -72,23 +202,244 @@ pub struct MenuItem {
     pub submenu: Vec<MenuItem>,
 }
 
+impl Default for MenuItem {
+    /// An item with id 0 and every property at its default value from the spec.
+    /// Notably, the item is enabled and visible.
+    fn default() -> Self {
+        Self {
+            id: 0,
+            menu_type: MenuType::default(),
+            label: None,
+            mnemonic: None,
+            enabled: true,
+            visible: true,
+            icon_name: None,
+            icon_data: None,
+            shortcut: None,
+            toggle_type: ToggleType::default(),
+            toggle_state: ToggleState::default(),
+            children_display: None,
+            disposition: Disposition::default(),
+            submenu: vec![],
+        }
+    }
+}
+
+impl MenuItem {
+    /// Applies a property change sent by the application to the item.
+    pub fn apply_diff(&mut self, diff: &MenuDiff) {
+        let update = &diff.update;
+
+        if let Some(label) = &update.label {
+            self.label.clone_from(label);
+        }
+        if let Some(mnemonic) = update.mnemonic {
+            self.mnemonic = mnemonic;
+        }
+        if let Some(enabled) = update.enabled {
+            self.enabled = enabled;
+        }
+        if let Some(visible) = update.visible {
+            self.visible = visible;
+        }
+        if let Some(icon_name) = &update.icon_name {
+            self.icon_name.clone_from(icon_name);
+        }
+        if let Some(icon_data) = &update.icon_data {
+            self.icon_data.clone_from(icon_data);
+        }
+        if let Some(toggle_state) = update.toggle_state {
+            self.toggle_state = toggle_state;
+        }
+        if let Some(disposition) = update.disposition {
+            self.disposition = disposition;
+        }
+        if let Some(menu_type) = update.menu_type {
+            self.menu_type = menu_type;
+        }
+        if let Some(shortcut) = &update.shortcut {
+            self.shortcut.clone_from(shortcut);
+        }
+        if let Some(toggle_type) = update.toggle_type {
+            self.toggle_type = toggle_type;
+        }
+        if let Some(children_display) = &update.children_display {
+            self.children_display.clone_from(children_display);
+        }
+
+        for property in &diff.remove {
+            self.reset_property(property);
+        }
+    }
+
+    /// Sets the property `name` back to its default value.
+    fn reset_property(&mut self, name: &str) {
+        let default = Self::default();
+
+        match name {
+            "label" => {
+                self.label = default.label;
+                self.mnemonic = default.mnemonic;
+            }
+            "enabled" => self.enabled = default.enabled,
+            "visible" => self.visible = default.visible,
+            "icon-name" => self.icon_name = default.icon_name,
+            "icon-data" => self.icon_data = default.icon_data,
+            "toggle-state" => self.toggle_state = default.toggle_state,
+            "disposition" => self.disposition = default.disposition,
+            "type" => self.menu_type = default.menu_type,
+            "shortcut" => self.shortcut = default.shortcut,
+            "toggle-type" => self.toggle_type = default.toggle_type,
+            "children-display" => self.children_display = default.children_display,
+            _ => {}
+        }
+    }
+
+    /// Checks whether the item is a separator,
+    /// which should be drawn as a line rather than as a clickable row.
+    #[must_use]
+    pub fn is_separator(&self) -> bool {
+        self.menu_type == MenuType::Separator
+    }
+
+    /// Checks whether the item opens a submenu.
+    ///
+    /// This is true as soon as the application announces the submenu,
+    /// before its children have been fetched,
+    /// so it can be used to decide when to send `AboutToShow`.
+    #[must_use]
+    pub fn has_submenu(&self) -> bool {
+        self.children_display.as_deref() == Some("submenu") || !self.submenu.is_empty()
+    }
+
+    /// Gets the shortcut of the item as shown next to its label,
+    /// for example `Ctrl+Q, Alt+X`.
+    #[must_use]
+    pub fn shortcut_label(&self) -> Option<String> {
+        self.shortcut.as_ref().map(|shortcut| {
+            shortcut
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
+    /// Gets the icon of the item.
+    ///
+    /// The icon name is preferred over the PNG data,
+    /// and empty values are treated as unset.
+    #[must_use]
+    pub fn icon(&self) -> Icon {
+        match (&self.icon_name, &self.icon_data) {
+            (Some(name), _) if !name.is_empty() => Icon::Named(name.clone()),
+            (_, Some(data)) if !data.is_empty() => Icon::Png(data.clone()),
+            _ => Icon::None,
+        }
+    }
+}
+
+/// A single key press of a menu item shortcut.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct Shortcut {
+    /// The modifiers held down.
+    pub modifiers: Vec<Modifier>,
+    /// The name of the key pressed, for example `Q` or `F1`.
+    pub key: String,
+}
+
+impl Display for Shortcut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{modifier}+")?;
+        }
+        f.write_str(&self.key)
+    }
+}
+
+impl Shortcut {
+    /// Builds a key press from its modifiers followed by its key,
+    /// as sent by the application.
+    fn from_keys(keys: &[&str]) -> Option<Self> {
+        let (key, modifiers) = keys.split_last()?;
+
+        Some(Self {
+            modifiers: modifiers
+                .iter()
+                .map(|&modifier| Modifier::from(modifier))
+                .collect(),
+            key: (*key).to_string(),
+        })
+    }
+}
+
+/// A modifier key of a [`Shortcut`].
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub enum Modifier {
+    Control,
+    Alt,
+    Shift,
+    Super,
+    /// A modifier outside the specification, kept as sent.
+    Other(String),
+}
+
+impl From<&str> for Modifier {
+    fn from(value: &str) -> Self {
+        match value {
+            "Control" => Self::Control,
+            "Alt" => Self::Alt,
+            "Shift" => Self::Shift,
+            "Super" => Self::Super,
+            _ => Self::Other(value.to_string()),
+        }
+    }
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Control => f.write_str("Ctrl"),
+            Self::Alt => f.write_str("Alt"),
+            Self::Shift => f.write_str("Shift"),
+            Self::Super => f.write_str("Super"),
+            Self::Other(modifier) => f.write_str(modifier),
+        }
+    }
+}
+
+/// Splits `items` into groups of consecutive radio items,
+/// of which only one should be shown as selected.
+///
+/// Groups are ended by any item which is not a radio item, including separators.
+#[must_use]
+pub fn radio_groups(items: &[MenuItem]) -> Vec<&[MenuItem]> {
+    items
+        .split(|item| item.toggle_type != ToggleType::Radio)
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+/// A change to the properties of a single menu item,
+/// sent by the application in `ItemsPropertiesUpdated`.
+///
+/// See [`TrayMenu::apply_diffs`].
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct MenuDiff {
     pub id: i32,
     pub update: MenuItemUpdate,
+    /// The names of the properties removed from the item,
+    /// which go back to their default values.
     pub remove: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
 pub struct MenuItemUpdate {
-    /// Text of the item, except that:
-    ///  - two consecutive underscore characters "__" are displayed as a
-    ///    single underscore,
-    ///  - any remaining underscore characters are not displayed at all,
-    ///  - the first of those remaining underscore characters (unless it is
-    ///    the last character in the string) indicates that the following
-    ///    character is the access key.
+    /// Text of the item, as it should be displayed.
+    /// See [`MenuItem::label`].
     pub label: Option<Option<String>>,
+    /// The access key of the item, updated along with its label.
+    pub mnemonic: Option<Option<char>>,
     /// Whether the item can be activated or not.
     pub enabled: Option<bool>,
     /// True if the item is visible in the menu.
@@ -110,6 +461,14 @@ pub struct MenuItemUpdate {
     /// user should be presented.
     /// See [`Disposition`]
     pub disposition: Option<Disposition>,
+    /// Either a standard menu item or a separator.
+    pub menu_type: Option<MenuType>,
+    /// See [`MenuItem::shortcut`].
+    pub shortcut: Option<Option<Vec<Shortcut>>>,
+    /// See [`ToggleType`].
+    pub toggle_type: Option<ToggleType>,
+    /// See [`MenuItem::children_display`].
+    pub children_display: Option<Option<String>>,
 }
 
 #[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq, Default)]
@@ -153,14 +512,17 @@ impl From<&str> for ToggleType {
 }
 
 /// Describe the current state of a "togglable" item.
+///
+/// Items without the property are [`ToggleState::Indeterminate`],
+/// matching the `-1` default of the specification.
 #[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq, Default)]
 pub enum ToggleState {
     /// This item is toggled
-    #[default]
     On,
     /// Item is not toggled
     Off,
-    /// Item is not toggalble
+    /// Item is neither toggled nor untoggled, or cannot be toggled
+    #[default]
     Indeterminate,
 }
 
@@ -174,6 +536,10 @@ impl From<i32> for ToggleState {
     }
 }
 
+/// How a menu item should be styled,
+/// based on the kind of information it presents.
+///
+/// Items without the property, or with an unknown value, are [`Disposition::Normal`].
 #[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq, Default)]
 pub enum Disposition {
     /// a standard menu item
@@ -198,70 +564,156 @@ impl From<&str> for Disposition {
     }
 }
 
-impl TryFrom<MenuLayout> for TrayMenu {
-    type Error = Error;
+/// The default number of levels of submenus parsed from a layout
+/// before it is rejected as too deep.
+pub(crate) const MAX_MENU_DEPTH: usize = 32;
+
+/// Checks made while parsing a layout,
+/// so a broken or malicious application cannot make parsing recurse without end.
+struct LayoutGuard {
+    max_depth: usize,
+    ids: HashSet<i32>,
+}
+
+impl LayoutGuard {
+    fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            ids: HashSet::new(),
+        }
+    }
+
+    /// Checks an item found `depth` levels down has not been seen before.
+    fn check(&mut self, id: i32, depth: usize) -> Result<()> {
+        if depth > self.max_depth {
+            return Err(Error::MenuTooDeep(self.max_depth));
+        }
+
+        if !self.ids.insert(id) {
+            return Err(Error::DuplicateMenuId(id));
+        }
+
+        Ok(())
+    }
+}
+
+impl TrayMenu {
+    /// Sanitizes the labels of the items at every level of the menu,
+    /// see [`sanitize_text`].
+    pub(crate) fn sanitize(&mut self, max_length: usize) {
+        for item in &mut self.submenus {
+            item.sanitize(max_length);
+        }
+    }
+
+    /// Parses a layout, rejecting it if nested more than `max_depth` levels deep
+    /// or if any item id appears more than once.
+    pub(crate) fn from_layout(value: MenuLayout, max_depth: usize) -> Result<Self> {
+        let mut guard = LayoutGuard::new(max_depth);
+        guard.check(value.fields.id, 0)?;
 
-    fn try_from(value: MenuLayout) -> Result<Self> {
         let submenus = value
             .fields
             .submenus
             .iter()
-            .map(MenuItem::try_from)
-            .collect::<std::result::Result<_, _>>()?;
+            .map(|value| MenuItem::parse(value, &mut guard, 1))
+            .collect::<Result<_>>()?;
 
         Ok(Self {
             id: value.id,
+            version: None,
+            icon_theme_path: vec![],
+            status: MenuStatus::default(),
+            text_direction: TextDirection::default(),
             submenus,
         })
     }
 }
 
+impl TryFrom<MenuLayout> for TrayMenu {
+    type Error = Error;
+
+    fn try_from(value: MenuLayout) -> Result<Self> {
+        Self::from_layout(value, MAX_MENU_DEPTH)
+    }
+}
+
 impl TryFrom<&OwnedValue> for MenuItem {
     type Error = Error;
 
     fn try_from(value: &OwnedValue) -> Result<Self> {
+        Self::parse(value, &mut LayoutGuard::new(MAX_MENU_DEPTH), 1)
+    }
+}
+
+impl MenuItem {
+    /// Sanitizes the labels of the item and its submenu, see [`sanitize_text`].
+    pub(crate) fn sanitize(&mut self, max_length: usize) {
+        if let Some(label) = &mut self.label {
+            sanitize_text(label, max_length);
+        }
+
+        for item in &mut self.submenu {
+            item.sanitize(max_length);
+        }
+    }
+
+    /// Builds an item without children from its `properties`,
+    /// as returned by `GetGroupProperties`.
+    pub(crate) fn from_properties(
+        id: i32,
+        properties: HashMap<String, OwnedValue>,
+    ) -> Result<Self> {
+        let submenus: Vec<Value> = vec![];
+        let value = OwnedValue::try_from(Value::from((id, properties, submenus)))?;
+
+        Self::try_from(&value)
+    }
+
+    /// Parses an item found `depth` levels down a layout, along with its submenus.
+    fn parse(value: &OwnedValue, guard: &mut LayoutGuard, depth: usize) -> Result<Self> {
         let structure = value.downcast_ref::<&Structure>()?;
 
         let mut fields = structure.fields().iter();
 
-        // defaults for enabled/visible are true
-        // and setting here avoids having to provide a full `Default` impl
-        let mut menu = MenuItem {
-            enabled: true,
-            visible: true,
-            ..Default::default()
-        };
+        let mut menu = MenuItem::default();
 
         if let Some(Value::I32(id)) = fields.next() {
             menu.id = *id;
         }
 
+        guard.check(menu.id, depth)?;
+
         if let Some(Value::Dict(dict)) = fields.next() {
-            menu.children_display = dict
-                .get::<&str, &str>(&"children-display")?
-                .map(str::to_string);
+            menu.children_display = dict_get::<&str>(dict, "children-display")?.map(str::to_string);
 
             // see: https://github.com/gnustep/libs-dbuskit/blob/4dc9b56216e46e0e385b976b0605b965509ebbbd/Bundles/DBusMenu/com.canonical.dbusmenu.xml#L76
-            menu.label = dict
-                .get::<&str, &str>(&"label")?
-                .map(|label| label.replace('_', ""));
+            if let Some(label) = dict_get::<&str>(dict, "label")? {
+                let (label, mnemonic) = parse_mnemonic(label);
+                menu.label = Some(label);
+                menu.mnemonic = mnemonic;
+            }
 
-            if let Some(enabled) = dict.get::<&str, bool>(&"enabled")? {
+            if let Some(enabled) = dict_get::<bool>(dict, "enabled")? {
                 menu.enabled = enabled;
             }
 
-            if let Some(visible) = dict.get::<&str, bool>(&"visible")? {
+            if let Some(visible) = dict_get::<bool>(dict, "visible")? {
                 menu.visible = visible;
             }
 
-            menu.icon_name = dict.get::<&str, &str>(&"icon-name")?.map(str::to_string);
+            menu.icon_name = dict_get::<&str>(dict, "icon-name")?.map(str::to_string);
 
-            if let Some(array) = dict.get::<&str, &Array>(&"icon-data")? {
+            if let Some(array) = dict_get::<&Array>(dict, "icon-data")? {
                 menu.icon_data = Some(get_icon_data(array)?);
             }
 
-            if let Some(disposition) = dict
-                .get::<&str, &str>(&"disposition")
+            menu.shortcut = dict_get::<&Array>(dict, "shortcut")
+                .ok()
+                .flatten()
+                .and_then(|array| get_shortcut(array).ok());
+
+            if let Some(disposition) = dict_get::<&str>(dict, "disposition")
                 .ok()
                 .flatten()
                 .map(Disposition::from)
@@ -269,22 +721,19 @@ impl TryFrom<&OwnedValue> for MenuItem {
                 menu.disposition = disposition;
             }
 
-            menu.toggle_state = dict
-                .get::<&str, i32>(&"toggle-state")
+            menu.toggle_state = dict_get::<i32>(dict, "toggle-state")
                 .ok()
                 .flatten()
                 .map(ToggleState::from)
                 .unwrap_or_default();
 
-            menu.toggle_type = dict
-                .get::<&str, &str>(&"toggle-type")
+            menu.toggle_type = dict_get::<&str>(dict, "toggle-type")
                 .ok()
                 .flatten()
                 .map(ToggleType::from)
                 .unwrap_or_default();
 
-            menu.menu_type = dict
-                .get::<&str, &str>(&"type")
+            menu.menu_type = dict_get::<&str>(dict, "type")
                 .ok()
                 .flatten()
                 .map(MenuType::from)
@@ -294,8 +743,14 @@ impl TryFrom<&OwnedValue> for MenuItem {
         if let Some(Value::Array(array)) = fields.next() {
             let mut submenu = vec![];
             for value in array.iter() {
+                // children are variants, which are not unwrapped by `downcast_ref`
+                let value = match value {
+                    Value::Value(value) => value,
+                    value => value,
+                };
+
                 let value = OwnedValue::try_from(value)?;
-                let menu = MenuItem::try_from(&value)?;
+                let menu = MenuItem::parse(&value, guard, depth + 1)?;
                 submenu.push(menu);
             }
 
@@ -352,10 +807,16 @@ impl TryFrom<UpdatedProps<'_>> for MenuItemUpdate {
             None
         };
 
+        let label = dict
+            .get("label")
+            .map(|v| v.downcast_ref::<&str>().ok().map(parse_mnemonic));
+
         Ok(Self {
-            label: dict
-                .get("label")
-                .map(|v| v.downcast_ref::<&str>().map(ToString::to_string).ok()),
+            label: label
+                .as_ref()
+                .map(|label| label.as_ref().map(|(label, _)| label.clone())),
+
+            mnemonic: label.map(|label| label.and_then(|(_, mnemonic)| mnemonic)),
 
             enabled: dict
                 .get("enabled")
@@ -380,13 +841,363 @@ impl TryFrom<UpdatedProps<'_>> for MenuItemUpdate {
                 .get("disposition")
                 .and_then(|v| Value::downcast_ref::<&str>(v).ok())
                 .map(Disposition::from),
+
+            menu_type: dict
+                .get("type")
+                .and_then(|v| Value::downcast_ref::<&str>(v).ok())
+                .map(MenuType::from),
+
+            shortcut: dict.get("shortcut").map(|v| {
+                v.downcast_ref::<&Array>()
+                    .ok()
+                    .and_then(|array| get_shortcut(array).ok())
+            }),
+
+            toggle_type: dict
+                .get("toggle-type")
+                .and_then(|v| Value::downcast_ref::<&str>(v).ok())
+                .map(ToggleType::from),
+
+            children_display: dict
+                .get("children-display")
+                .map(|v| v.downcast_ref::<&str>().map(ToString::to_string).ok()),
         })
     }
 }
 
+/// Splits a label into the text to display and its access key.
+///
+/// "__" is a literal underscore, any other underscore is removed,
+/// and the first of those marks the following character as the access key.
+fn parse_mnemonic(label: &str) -> (String, Option<char>) {
+    let mut text = String::with_capacity(label.len());
+    let mut mnemonic = None;
+
+    let mut chars = label.chars();
+    while let Some(char) = chars.next() {
+        if char != '_' {
+            text.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            Some('_') => text.push('_'),
+            Some(next) => {
+                mnemonic.get_or_insert(next);
+                text.push(next);
+            }
+            None => {}
+        }
+    }
+
+    (text, mnemonic)
+}
+
+/// Parses a shortcut from its array of key presses,
+/// each of which is an array of key names.
+fn get_shortcut(array: &Array) -> Result<Vec<Shortcut>> {
+    array
+        .iter()
+        .map(|keys| {
+            let keys = keys
+                .downcast_ref::<&Array>()?
+                .iter()
+                .map(|key| key.downcast_ref::<&str>().map_err(Into::into))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Shortcut::from_keys(&keys))
+        })
+        .filter_map(Result::transpose)
+        .collect()
+}
+
 fn get_icon_data(array: &Array) -> Result<Vec<u8>> {
     array
         .iter()
         .map(|v| v.downcast_ref::<u8>().map_err(Into::into))
         .collect::<Result<Vec<_>>>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_item(props: Vec<(&str, Value)>) -> OwnedValue {
+        let props = props.into_iter().collect::<HashMap<_, _>>();
+        let submenus: Vec<Value> = vec![];
+
+        OwnedValue::try_from(Value::from((1i32, props, submenus))).expect("value should convert")
+    }
+
+    #[test]
+    fn parse_disposition() {
+        let item = make_item(vec![("disposition", Value::from("alert"))]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert_eq!(Disposition::Alert, menu.disposition);
+
+        let item = make_item(vec![("disposition", Value::from("unknown"))]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert_eq!(Disposition::Normal, menu.disposition);
+
+        let item = make_item(vec![]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert_eq!(Disposition::Normal, menu.disposition);
+    }
+
+    #[test]
+    fn parse_separator() {
+        let item = make_item(vec![("type", Value::from("separator"))]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(menu.is_separator());
+
+        let item = make_item(vec![("label", Value::from("_Quit"))]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(!menu.is_separator());
+    }
+
+    #[test]
+    fn submenu_before_children() {
+        let item = make_item(vec![("children-display", Value::from("submenu"))]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(menu.submenu.is_empty());
+        assert!(menu.has_submenu());
+
+        let item = make_item(vec![]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(!menu.has_submenu());
+    }
+
+    #[test]
+    fn visible_items_only() {
+        let item = |id, visible| MenuItem {
+            id,
+            visible,
+            ..Default::default()
+        };
+        let separator = || MenuItem {
+            menu_type: MenuType::Separator,
+            visible: true,
+            ..Default::default()
+        };
+
+        let mut parent = item(3, true);
+        parent.submenu = vec![item(4, false), item(5, true)];
+
+        let mut menu = TrayMenu {
+            id: 0,
+            version: None,
+            icon_theme_path: vec![],
+            status: MenuStatus::default(),
+            text_direction: TextDirection::default(),
+            submenus: vec![
+                separator(),
+                item(1, true),
+                separator(),
+                item(2, false),
+                separator(),
+                parent,
+                separator(),
+            ],
+        };
+        menu.retain_visible();
+
+        let ids = menu
+            .submenus
+            .iter()
+            .map(|item| (item.id, item.is_separator()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(1, false), (0, true), (3, false)], ids);
+        assert_eq!(1, menu.submenus[2].submenu.len());
+    }
+
+    #[test]
+    fn layout_guard() {
+        fn nest(id: i32, children: Vec<Value<'static>>) -> Value<'static> {
+            let props = HashMap::<&str, Value>::new();
+            let children = children
+                .into_iter()
+                .map(|child| Value::Value(Box::new(child)))
+                .collect::<Vec<_>>();
+            Value::from((id, props, children))
+        }
+
+        let deep = (2..=4)
+            .rev()
+            .fold(nest(5, vec![]), |child, id| nest(id, vec![child]));
+        let item = OwnedValue::try_from(deep).expect("value should convert");
+
+        assert!(MenuItem::parse(&item, &mut LayoutGuard::new(4), 1).is_ok());
+        assert!(matches!(
+            MenuItem::parse(&item, &mut LayoutGuard::new(3), 1),
+            Err(Error::MenuTooDeep(3))
+        ));
+
+        let cyclic = nest(1, vec![nest(2, vec![]), nest(1, vec![])]);
+        let item = OwnedValue::try_from(cyclic).expect("value should convert");
+        assert!(matches!(
+            MenuItem::try_from(&item),
+            Err(Error::DuplicateMenuId(1))
+        ));
+    }
+
+    #[test]
+    fn parse_group_properties() {
+        let properties = HashMap::from([
+            (
+                "label".to_string(),
+                OwnedValue::try_from(Value::from("_Open")).expect("value should convert"),
+            ),
+            ("enabled".to_string(), OwnedValue::from(false)),
+        ]);
+
+        let item = MenuItem::from_properties(7, properties).expect("item should parse");
+        assert_eq!(7, item.id);
+        assert_eq!(Some("Open"), item.label.as_deref());
+        assert!(!item.enabled);
+        assert!(item.visible);
+    }
+
+    #[test]
+    fn apply_diffs() {
+        let mut child = MenuItem {
+            id: 2,
+            enabled: true,
+            visible: true,
+            label: Some("Mute".to_string()),
+            ..Default::default()
+        };
+        child.icon_name = Some("audio-volume-high".to_string());
+
+        let mut menu = TrayMenu {
+            id: 0,
+            version: None,
+            icon_theme_path: vec![],
+            status: MenuStatus::default(),
+            text_direction: TextDirection::default(),
+            submenus: vec![MenuItem {
+                id: 1,
+                submenu: vec![child],
+                ..Default::default()
+            }],
+        };
+
+        let updated = UpdatedProps {
+            id: 2,
+            fields: HashMap::from([
+                ("label", Value::from("_Unmute")),
+                ("enabled", Value::from(false)),
+            ]),
+        };
+
+        let diffs = Vec::<MenuDiff>::try_from(PropertiesUpdate {
+            updated: vec![updated],
+            removed: vec![RemovedProps {
+                id: 2,
+                fields: vec!["icon-name"],
+            }],
+        })
+        .expect("diff should parse");
+
+        menu.apply_diffs(&diffs);
+
+        let item = menu.find_item(2).expect("item should be present");
+        assert_eq!(Some("Unmute"), item.label.as_deref());
+        assert_eq!(Some('U'), item.mnemonic);
+        assert!(!item.enabled);
+        assert!(item.visible);
+        assert_eq!(None, item.icon_name);
+    }
+
+    #[test]
+    fn parse_toggle() {
+        let item = make_item(vec![
+            ("toggle-type", Value::from("checkmark")),
+            ("toggle-state", Value::from(0i32)),
+        ]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert_eq!(ToggleType::Checkmark, menu.toggle_type);
+        assert_eq!(ToggleState::Off, menu.toggle_state);
+
+        let item = make_item(vec![]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert_eq!(ToggleType::CannotBeToggled, menu.toggle_type);
+        assert_eq!(ToggleState::Indeterminate, menu.toggle_state);
+    }
+
+    #[test]
+    fn label_mnemonic() {
+        assert_eq!(("Quit".to_string(), Some('Q')), parse_mnemonic("_Quit"));
+        assert_eq!(
+            ("Save As".to_string(), Some('A')),
+            parse_mnemonic("Save _As")
+        );
+        assert_eq!(
+            ("snake_case".to_string(), None),
+            parse_mnemonic("snake__case")
+        );
+        assert_eq!(("Open".to_string(), Some('O')), parse_mnemonic("_Op_en_"));
+    }
+
+    #[test]
+    fn parse_shortcut() {
+        let item = make_item(vec![(
+            "shortcut",
+            Value::from(vec![vec!["Control", "Q"], vec!["Alt", "Shift", "X"]]),
+        )]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+
+        let shortcut = menu.shortcut.as_deref().expect("shortcut should parse");
+        assert_eq!(vec![Modifier::Control], shortcut[0].modifiers);
+        assert_eq!("Q", shortcut[0].key);
+        assert_eq!(
+            Some("Ctrl+Q, Alt+Shift+X"),
+            menu.shortcut_label().as_deref()
+        );
+
+        let item = make_item(vec![]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert_eq!(None, menu.shortcut_label());
+    }
+
+    #[test]
+    fn group_radio_items() {
+        let radio = |id| MenuItem {
+            id,
+            toggle_type: ToggleType::Radio,
+            ..Default::default()
+        };
+        let separator = MenuItem {
+            menu_type: MenuType::Separator,
+            ..Default::default()
+        };
+
+        let items = [radio(1), radio(2), separator, radio(3), MenuItem::default()];
+        let groups = radio_groups(&items)
+            .iter()
+            .map(|group| group.iter().map(|item| item.id).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![vec![1, 2], vec![3]], groups);
+    }
+
+    #[test]
+    fn parse_icon() {
+        let item = make_item(vec![(
+            "icon-data",
+            Value::from(vec![0x89u8, b'P', b'N', b'G']),
+        )]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(matches!(menu.icon(), Icon::Png(data) if data.len() == 4));
+
+        let item = make_item(vec![
+            ("icon-name", Value::from("media-playback-start")),
+            ("icon-data", Value::from(vec![0x89u8, b'P', b'N', b'G'])),
+        ]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(matches!(menu.icon(), Icon::Named(name) if name == "media-playback-start"));
+
+        let item = make_item(vec![]);
+        let menu = MenuItem::try_from(&item).expect("item should parse");
+        assert!(matches!(menu.icon(), Icon::None));
+    }
+}
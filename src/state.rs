@@ -0,0 +1,294 @@
+use crate::client::{Event, UpdateEvent};
+use crate::item::StatusNotifierItem;
+use crate::menu::TrayMenu;
+use std::collections::HashMap;
+
+/// Each item keyed on its address, along with its menu if loaded.
+pub(crate) type State = HashMap<String, (StatusNotifierItem, Option<TrayMenu>)>;
+
+/// The set of items in the tray, along with their menus if loaded,
+/// maintained by applying each [`Event`] received from the client.
+///
+/// The client keeps its own state using the same logic,
+/// so a `TrayState` fed from [`Client::subscribe`](crate::client::Client::subscribe)
+/// matches [`Client::items`](crate::client::Client::items).
+///
+/// ```no_run
+/// use system_tray::client::Client;
+/// use system_tray::state::TrayState;
+///
+/// # async fn run() -> system_tray::error::Result<()> {
+/// let client = Client::new().await?;
+/// let mut tray_rx = client.subscribe();
+/// let mut state = TrayState::new();
+///
+/// while let Ok(event) = tray_rx.recv().await {
+///     state.apply(&event);
+///     println!("{} items", state.items().len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TrayState {
+    items: State,
+}
+
+impl TrayState {
+    /// Creates an empty state.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the state with the change described by `event`.
+    pub fn apply(&mut self, event: &Event) {
+        apply(&mut self.items, event);
+    }
+
+    /// Gets all items, keyed on their address,
+    /// including their menus if loaded.
+    #[must_use]
+    pub fn items(&self) -> &HashMap<String, (StatusNotifierItem, Option<TrayMenu>)> {
+        &self.items
+    }
+
+    /// Gets the item at `address`.
+    #[must_use]
+    pub fn item(&self, address: &str) -> Option<&StatusNotifierItem> {
+        self.items.get(address).map(|(item, _)| item)
+    }
+
+    /// Gets the menu for the item at `address`, if loaded.
+    #[must_use]
+    pub fn menu(&self, address: &str) -> Option<&TrayMenu> {
+        self.items.get(address).and_then(|(_, menu)| menu.as_ref())
+    }
+
+    /// Consumes the state, returning all items.
+    #[must_use]
+    pub fn into_items(self) -> HashMap<String, (StatusNotifierItem, Option<TrayMenu>)> {
+        self.items
+    }
+}
+
+/// Updates `items` with the change described by `event`.
+pub(crate) fn apply(items: &mut State, event: &Event) {
+    match event {
+        Event::Add(address, item) => {
+            items.insert(address.clone(), ((**item).clone(), None));
+        }
+        Event::Update(address, update) => {
+            if let Some((item, menu)) = items.get_mut(address) {
+                apply_update(item, menu, update);
+            }
+        }
+        Event::Remove(address) => {
+            items.remove(address);
+        }
+        Event::Warning(..) => {}
+        Event::Reset(new_items) | Event::Reconnected(new_items) => {
+            // menus are kept for items which are still present,
+            // as the reset itself carries none
+            let mut old_items = std::mem::take(items);
+            for (address, item) in new_items {
+                let menu = old_items.remove(address).and_then(|(_, menu)| menu);
+                items.insert(address.clone(), (item.clone(), menu));
+            }
+        }
+    }
+}
+
+/// Checks whether an icon `update` leaves `item` as it is.
+///
+/// Applications often announce a new icon without changing it,
+/// so these updates are not sent on.
+pub(crate) fn is_unchanged_icon(item: &StatusNotifierItem, update: &UpdateEvent) -> bool {
+    match update {
+        UpdateEvent::Icon(name) => item.icon_name == *name,
+        UpdateEvent::OverlayIcon(name) => item.overlay_icon_name == *name,
+        UpdateEvent::AttentionIcon(name) => item.attention_icon_name == *name,
+        UpdateEvent::IconPixmap(pixmaps) => item.icon_pixmap == *pixmaps,
+        UpdateEvent::OverlayIconPixmap(pixmaps) => item.overlay_icon_pixmap == *pixmaps,
+        UpdateEvent::AttentionIconPixmap(pixmaps) => item.attention_icon_pixmap == *pixmaps,
+        _ => false,
+    }
+}
+
+/// Gets the updates which change `old` into `new`,
+/// for each property with an [`UpdateEvent`] other than the menu.
+pub(crate) fn item_updates(old: &StatusNotifierItem, new: &StatusNotifierItem) -> Vec<UpdateEvent> {
+    [
+        UpdateEvent::Title(new.title.clone()),
+        UpdateEvent::Status(new.status),
+        UpdateEvent::Tooltip(new.tool_tip.clone()),
+        UpdateEvent::Icon(new.icon_name.clone()),
+        UpdateEvent::OverlayIcon(new.overlay_icon_name.clone()),
+        UpdateEvent::AttentionIcon(new.attention_icon_name.clone()),
+        UpdateEvent::IconPixmap(new.icon_pixmap.clone()),
+        UpdateEvent::OverlayIconPixmap(new.overlay_icon_pixmap.clone()),
+        UpdateEvent::AttentionIconPixmap(new.attention_icon_pixmap.clone()),
+    ]
+    .into_iter()
+    .filter(|update| match update {
+        UpdateEvent::Title(title) => old.title != *title,
+        UpdateEvent::Status(status) => old.status != *status,
+        UpdateEvent::Tooltip(tool_tip) => old.tool_tip != *tool_tip,
+        update => !is_unchanged_icon(old, update),
+    })
+    .collect()
+}
+
+/// Updates an item and its menu with the change described by `update`.
+fn apply_update(item: &mut StatusNotifierItem, menu: &mut Option<TrayMenu>, update: &UpdateEvent) {
+    match update {
+        UpdateEvent::AttentionIcon(name) => item.attention_icon_name.clone_from(name),
+        UpdateEvent::Icon(name) => item.icon_name.clone_from(name),
+        UpdateEvent::OverlayIcon(name) => item.overlay_icon_name.clone_from(name),
+        UpdateEvent::Status(status) => item.status = *status,
+        UpdateEvent::Title(title) => item.title.clone_from(title),
+        UpdateEvent::Tooltip(tooltip) => item.tool_tip.clone_from(tooltip),
+        UpdateEvent::IconPixmap(pixmaps) => item.icon_pixmap.clone_from(pixmaps),
+        UpdateEvent::OverlayIconPixmap(pixmaps) => item.overlay_icon_pixmap.clone_from(pixmaps),
+        UpdateEvent::AttentionIconPixmap(pixmaps) => {
+            item.attention_icon_pixmap.clone_from(pixmaps);
+        }
+        UpdateEvent::Menu(new_menu) => *menu = Some(new_menu.clone()),
+        UpdateEvent::MenuConnect(path) => {
+            // the cached layout belongs to the previous menu
+            if item.menu.as_ref() != Some(path) {
+                *menu = None;
+            }
+
+            item.menu = Some(path.clone());
+        }
+        UpdateEvent::MenuStatus(status) => {
+            if let Some(menu) = menu {
+                menu.status = *status;
+            }
+        }
+        UpdateEvent::MenuTextDirection(text_direction) => {
+            if let Some(menu) = menu {
+                menu.text_direction = *text_direction;
+            }
+        }
+        UpdateEvent::MenuDiff(diffs) => {
+            if let Some(menu) = menu {
+                menu.apply_diffs(diffs);
+            }
+        }
+        UpdateEvent::MenuClosed => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::{Category, IconPixmap, Status};
+    use crate::menu::{MenuStatus, TextDirection};
+
+    fn make_item(id: &str) -> StatusNotifierItem {
+        StatusNotifierItem {
+            id: id.to_string(),
+            category: Category::ApplicationStatus,
+            title: None,
+            status: Status::Active,
+            window_id: 0,
+            icon_theme_path: None,
+            icon_name: None,
+            icon_pixmap: None,
+            overlay_icon_name: None,
+            overlay_icon_pixmap: None,
+            attention_icon_name: None,
+            attention_icon_pixmap: None,
+            attention_movie_name: None,
+            tool_tip: None,
+            item_is_menu: false,
+            menu: None,
+        }
+    }
+
+    #[test]
+    fn apply_sequence() {
+        let mut state = TrayState::new();
+
+        state.apply(&Event::Add(":1.1".to_string(), make_item("a").into()));
+        state.apply(&Event::Add(":1.2".to_string(), make_item("b").into()));
+        state.apply(&Event::Update(
+            ":1.1".to_string(),
+            UpdateEvent::Icon(Some("icon".to_string())),
+        ));
+        state.apply(&Event::Update(
+            ":1.1".to_string(),
+            UpdateEvent::Menu(TrayMenu {
+                id: 1,
+                version: None,
+                icon_theme_path: vec![],
+                status: MenuStatus::default(),
+                text_direction: TextDirection::default(),
+                submenus: vec![],
+            }),
+        ));
+        state.apply(&Event::Remove(":1.2".to_string()));
+
+        assert_eq!(1, state.items().len());
+        let item = state.item(":1.1").expect("item should be present");
+        assert_eq!(Some("icon"), item.icon_name.as_deref());
+        assert!(state.menu(":1.1").is_some());
+
+        state.apply(&Event::Reset(vec![(":1.3".to_string(), make_item("c"))]));
+
+        assert_eq!(1, state.items().len());
+        assert!(state.item(":1.1").is_none());
+        assert!(state.item(":1.3").is_some());
+    }
+
+    #[test]
+    fn unchanged_icon() {
+        let pixmap = IconPixmap {
+            width: 1,
+            height: 1,
+            pixels: vec![0xff, 0x12, 0x34, 0x56],
+        };
+
+        let mut item = make_item("a");
+        item.icon_name = Some("icon".to_string());
+        item.icon_pixmap = Some(vec![pixmap.clone()]);
+
+        assert!(is_unchanged_icon(
+            &item,
+            &UpdateEvent::Icon(Some("icon".to_string()))
+        ));
+        assert!(is_unchanged_icon(
+            &item,
+            &UpdateEvent::IconPixmap(Some(vec![pixmap.clone()]))
+        ));
+
+        let mut changed = pixmap.clone();
+        changed.pixels[3] = 0x57;
+        assert!(!is_unchanged_icon(
+            &item,
+            &UpdateEvent::IconPixmap(Some(vec![changed]))
+        ));
+        assert!(!is_unchanged_icon(&item, &UpdateEvent::IconPixmap(None)));
+        assert!(!is_unchanged_icon(
+            &item,
+            &UpdateEvent::Icon(Some("other".to_string()))
+        ));
+    }
+
+    #[test]
+    fn updates_between_items() {
+        let old = make_item("a");
+        assert!(item_updates(&old, &old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.title = Some("title".to_string());
+        new.status = Status::Passive;
+        let updates = item_updates(&old, &new);
+
+        assert_eq!(2, updates.len());
+        assert!(matches!(&updates[0], UpdateEvent::Title(Some(title)) if title == "title"));
+        assert!(matches!(updates[1], UpdateEvent::Status(Status::Passive)));
+    }
+}
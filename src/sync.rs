@@ -0,0 +1,12 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks `mutex`, recovering the data if another thread panicked while holding it.
+///
+/// The state is shared with consumers through [`Client::items`](crate::client::Client::items),
+/// so a panic in consumer code must not stop the client,
+/// nor should a panic in one watcher method stop the others.
+/// Every change to the locked data is a single insert, update or removal,
+/// so it is still consistent after a panic.
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
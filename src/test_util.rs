@@ -0,0 +1,792 @@
+use crate::client::{Client, ClientBuilder};
+use crate::error::{Error, Result};
+use crate::names;
+use crate::sync::lock;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use zbus::message::Header;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Str, Value};
+use zbus::{interface, Connection};
+
+const WATCHER_INTERFACE: &str = "org.kde.StatusNotifierWatcher";
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+const MENU_PATH: &str = "/MenuBar";
+
+/// A menu item id, its properties and its children, as sent by `GetLayout`.
+type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+/// A private bus daemon, stopped when dropped.
+///
+/// Requires `dbus-daemon` to be installed.
+///
+/// ```no_run
+/// use system_tray::test_util::{MockItem, MockItemProperties, TestBus};
+///
+/// # async fn run() -> system_tray::error::Result<()> {
+/// let bus = TestBus::start()?;
+/// let client = bus.client().build().await?;
+/// let mut tray_rx = client.subscribe();
+///
+/// let item = MockItem::start(&bus, MockItemProperties::new("mock")).await?;
+/// item.register().await?;
+///
+/// let event = tray_rx.recv().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TestBus {
+    daemon: Child,
+    address: String,
+}
+
+impl TestBus {
+    /// Starts a new bus daemon.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if `dbus-daemon` cannot be run,
+    /// or does not report the address it is listening on.
+    pub fn start() -> Result<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(Error::TestBus)?;
+
+        let mut address = String::new();
+        if let Some(stdout) = daemon.stdout.take() {
+            BufReader::new(stdout)
+                .read_line(&mut address)
+                .map_err(Error::TestBus)?;
+        }
+
+        // the daemon is stopped when dropped, even if it failed to start
+        let bus = Self {
+            daemon,
+            address: address.trim().to_string(),
+        };
+
+        if bus.address.is_empty() {
+            return Err(Error::TestBus(std::io::Error::other(
+                "dbus-daemon did not print its address",
+            )));
+        }
+
+        Ok(bus)
+    }
+
+    /// Gets the address of the bus.
+    #[must_use]
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Creates a builder for a client connecting to this bus.
+    #[must_use]
+    pub fn client(&self) -> ClientBuilder {
+        Client::builder().bus_address(&self.address)
+    }
+
+    /// Opens a new connection to the bus.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if connecting to the bus fails.
+    pub async fn connect(&self) -> Result<Connection> {
+        let connection = zbus::connection::Builder::address(self.address.as_str())?
+            .build()
+            .await?;
+
+        Ok(connection)
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// The properties served by a [`MockItem`].
+#[derive(Debug, Clone, Default)]
+pub struct MockItemProperties {
+    pub id: String,
+    pub title: String,
+    pub status: String,
+    pub icon_name: String,
+    /// The items of the menu, which is served even if empty.
+    pub menu: Vec<MockMenuItem>,
+}
+
+impl MockItemProperties {
+    /// Creates the properties of an active item with `id` and an empty menu.
+    #[must_use]
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            status: String::from("Active"),
+            ..Self::default()
+        }
+    }
+}
+
+/// An item in the menu of a [`MockItem`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockMenuItem {
+    pub id: i32,
+    pub label: String,
+    pub submenu: Vec<MockMenuItem>,
+}
+
+impl MockMenuItem {
+    /// Creates a menu item without a submenu.
+    #[must_use]
+    pub fn new(id: i32, label: impl Into<String>) -> Self {
+        Self {
+            id,
+            label: label.into(),
+            submenu: vec![],
+        }
+    }
+
+    /// Sets the items of the submenu.
+    #[must_use]
+    pub fn with_submenu(mut self, submenu: Vec<MockMenuItem>) -> Self {
+        self.submenu = submenu;
+        self
+    }
+
+    fn find(&self, id: i32) -> Option<&Self> {
+        if self.id == id {
+            return Some(self);
+        }
+
+        self.submenu.iter().find_map(|item| item.find(id))
+    }
+}
+
+/// A call made to a [`MockItem`] or its menu.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    Activate { x: i32, y: i32 },
+    SecondaryActivate { x: i32, y: i32 },
+    ContextMenu { x: i32, y: i32 },
+    Scroll { delta: i32, orientation: String },
+    ProvideXdgActivationToken { token: String },
+    GetLayout { parent_id: i32 },
+    AboutToShow { id: i32 },
+    MenuEvent { id: i32, event_id: String },
+}
+
+#[derive(Debug)]
+struct MockState {
+    properties: MockItemProperties,
+    revision: u32,
+    calls: Vec<MockCall>,
+}
+
+/// A scripted `StatusNotifierItem` with a menu, served on its own connection to a [`TestBus`].
+///
+/// Properties are changed with the `set_*` methods,
+/// which send the signal announcing the change,
+/// and any other signal can be sent with [`MockItem::emit`].
+/// Calls made by the client are recorded, see [`MockItem::calls`].
+#[derive(Debug)]
+pub struct MockItem {
+    connection: Connection,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockItem {
+    /// Starts serving an item with `properties` on a new connection to `bus`.
+    ///
+    /// The item is not registered until [`MockItem::register`] is called.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if connecting to the bus fails.
+    pub async fn start(bus: &TestBus, properties: MockItemProperties) -> Result<Self> {
+        let state = Arc::new(Mutex::new(MockState {
+            properties,
+            revision: 1,
+            calls: vec![],
+        }));
+
+        let connection = zbus::connection::Builder::address(bus.address())?
+            .serve_at(
+                names::ITEM_OBJECT,
+                ItemInterface {
+                    state: state.clone(),
+                },
+            )?
+            .serve_at(
+                MENU_PATH,
+                MenuInterface {
+                    state: state.clone(),
+                },
+            )?
+            .build()
+            .await?;
+
+        Ok(Self { connection, state })
+    }
+
+    /// Gets the unique name of the connection serving the item.
+    #[must_use]
+    pub fn address(&self) -> String {
+        self.connection
+            .unique_name()
+            .map(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Gets the connection serving the item.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Serves the item at `path` as well, sharing its properties,
+    /// as applications do when they register their item again at another path.
+    ///
+    /// Signals are still sent from the original path.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if `path` is invalid or already served.
+    pub async fn serve_at(&self, path: &str) -> Result<()> {
+        let interface = ItemInterface {
+            state: self.state.clone(),
+        };
+
+        let added = self.connection.object_server().at(path, interface).await?;
+        if !added {
+            return Err(Error::InvalidAddress(
+                path.to_string(),
+                String::from("path is already served"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Serves the item at `path` instead of the default path,
+    /// so that calls to the default path fail.
+    ///
+    /// Signals are still sent from the default path.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if `path` is invalid or already served.
+    pub async fn move_to(&self, path: &str) -> Result<()> {
+        self.serve_at(path).await?;
+        self.connection
+            .object_server()
+            .remove::<ItemInterface, _>(names::ITEM_OBJECT)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Registers the item with the watcher on the bus.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if the watcher rejects the registration.
+    pub async fn register(&self) -> Result<()> {
+        self.connection
+            .call_method(
+                Some(names::WATCHER_BUS),
+                names::WATCHER_OBJECT,
+                Some(names::WATCHER_BUS),
+                "RegisterStatusNotifierItem",
+                &(self.address(),),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the calls made to the item and its menu so far, oldest first.
+    #[must_use]
+    pub fn calls(&self) -> Vec<MockCall> {
+        lock(&self.state).calls.clone()
+    }
+
+    /// Sets the title, and sends `NewTitle`.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn set_title(&self, title: impl Into<String>) -> Result<()> {
+        lock(&self.state).properties.title = title.into();
+        self.emit("NewTitle").await
+    }
+
+    /// Sets the icon name, and sends `NewIcon`.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn set_icon_name(&self, icon_name: impl Into<String>) -> Result<()> {
+        lock(&self.state).properties.icon_name = icon_name.into();
+        self.emit("NewIcon").await
+    }
+
+    /// Sets the status, and sends `NewStatus` carrying it.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn set_status(&self, status: impl Into<String>) -> Result<()> {
+        let status = status.into();
+        lock(&self.state).properties.status.clone_from(&status);
+
+        self.connection
+            .emit_signal(
+                None::<()>,
+                names::ITEM_OBJECT,
+                ITEM_INTERFACE,
+                "NewStatus",
+                &(status,),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the menu items, and sends `LayoutUpdated` for the root menu.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn set_menu(&self, menu: Vec<MockMenuItem>) -> Result<()> {
+        let revision = {
+            let mut state = lock(&self.state);
+            state.properties.menu = menu;
+            state.revision += 1;
+            state.revision
+        };
+
+        self.connection
+            .emit_signal(
+                None::<()>,
+                MENU_PATH,
+                MENU_INTERFACE,
+                "LayoutUpdated",
+                &(revision, 0),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Sends the item signal `member` without any arguments, such as `NewAttentionIcon`,
+    /// without changing any property.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn emit(&self, member: &str) -> Result<()> {
+        self.connection
+            .emit_signal(None::<()>, names::ITEM_OBJECT, ITEM_INTERFACE, member, &())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Closes the connection serving the item, which removes it from the tray.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if closing the connection fails.
+    pub async fn close(self) -> Result<()> {
+        self.connection.close().await?;
+        Ok(())
+    }
+}
+
+/// A scripted `StatusNotifierWatcher`, served on its own connection to a [`TestBus`].
+///
+/// The watcher must be started before any client on the bus,
+/// as a client starts its own watcher if the name is free.
+/// Items are registered by calling the watcher as normal, see [`MockItem::register`],
+/// or on command with [`MockWatcher::register_item`].
+/// Unlike a real watcher, addresses are passed on to the client as they were given,
+/// other than object paths registered on their own, which are prefixed with the caller.
+#[derive(Debug)]
+pub struct MockWatcher {
+    connection: Connection,
+    address: String,
+    items: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockWatcher {
+    /// Starts a watcher on a new connection to `bus`, taking the watcher name.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if connecting to the bus fails,
+    /// or the watcher name is already taken.
+    pub async fn start(bus: &TestBus) -> Result<Self> {
+        let items = Arc::default();
+        let connection = Self::connect(bus.address(), &items).await?;
+
+        Ok(Self {
+            connection,
+            address: bus.address().to_string(),
+            items,
+        })
+    }
+
+    async fn connect(address: &str, items: &Arc<Mutex<Vec<String>>>) -> Result<Connection> {
+        let connection = zbus::connection::Builder::address(address)?
+            .name(names::WATCHER_BUS)?
+            .serve_at(
+                names::WATCHER_OBJECT,
+                WatcherInterface {
+                    items: items.clone(),
+                },
+            )?
+            .build()
+            .await?;
+
+        Ok(connection)
+    }
+
+    /// Gets the addresses of the items registered, oldest first.
+    #[must_use]
+    pub fn items(&self) -> Vec<String> {
+        lock(&self.items).clone()
+    }
+
+    /// Registers the item at `address`, and sends `StatusNotifierItemRegistered`,
+    /// even if it is already registered.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn register_item(&self, address: &str) -> Result<()> {
+        register_item(&self.connection, &self.items, address).await?;
+        Ok(())
+    }
+
+    /// Drops the item at `address` if it is registered,
+    /// and sends `StatusNotifierItemUnregistered`.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if sending the signal fails.
+    pub async fn drop_item(&self, address: &str) -> Result<()> {
+        unregister_item(&self.connection, &self.items, address).await?;
+        Ok(())
+    }
+
+    /// Stops the watcher, forgetting its items, then starts it again on a new connection.
+    ///
+    /// If another watcher is waiting for the name, such as the one started by a client,
+    /// it takes over as it would when a real watcher restarts,
+    /// and the new connection waits for the name instead.
+    ///
+    /// # Errors
+    ///
+    /// The method will return an error if closing the connection or reconnecting fails.
+    pub async fn restart(&mut self) -> Result<()> {
+        self.connection.clone().close().await?;
+        lock(&self.items).clear();
+
+        let connection = zbus::connection::Builder::address(self.address.as_str())?
+            .serve_at(
+                names::WATCHER_OBJECT,
+                WatcherInterface {
+                    items: self.items.clone(),
+                },
+            )?
+            .build()
+            .await?;
+
+        // queued behind any watcher which took over while this one was gone
+        let flags: [zbus::fdo::RequestNameFlags; 0] = [];
+        connection
+            .request_name_with_flags(names::WATCHER_BUS, flags.into_iter().collect())
+            .await?;
+
+        self.connection = connection;
+        Ok(())
+    }
+}
+
+/// Adds `address` to `items` if it is not there,
+/// and sends `StatusNotifierItemRegistered` from `connection`.
+///
+/// The signal is sent for repeated registrations too, as some watchers do.
+async fn register_item(
+    connection: &Connection,
+    items: &Mutex<Vec<String>>,
+    address: &str,
+) -> zbus::Result<()> {
+    {
+        let mut items = lock(items);
+        if !items.iter().any(|item| item == address) {
+            items.push(address.to_string());
+        }
+    }
+
+    connection
+        .emit_signal(
+            None::<()>,
+            names::WATCHER_OBJECT,
+            WATCHER_INTERFACE,
+            "StatusNotifierItemRegistered",
+            &(address,),
+        )
+        .await
+}
+
+/// Removes `address` from `items`, and sends `StatusNotifierItemUnregistered` from `connection`
+/// if it was there.
+async fn unregister_item(
+    connection: &Connection,
+    items: &Mutex<Vec<String>>,
+    address: &str,
+) -> zbus::Result<()> {
+    {
+        let mut items = lock(items);
+        let Some(index) = items.iter().position(|item| item == address) else {
+            return Ok(());
+        };
+        items.remove(index);
+    }
+
+    connection
+        .emit_signal(
+            None::<()>,
+            names::WATCHER_OBJECT,
+            WATCHER_INTERFACE,
+            "StatusNotifierItemUnregistered",
+            &(address,),
+        )
+        .await
+}
+
+struct ItemInterface {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl ItemInterface {
+    fn record(&self, call: MockCall) {
+        lock(&self.state).calls.push(call);
+    }
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl ItemInterface {
+    fn activate(&self, x: i32, y: i32) {
+        self.record(MockCall::Activate { x, y });
+    }
+
+    fn secondary_activate(&self, x: i32, y: i32) {
+        self.record(MockCall::SecondaryActivate { x, y });
+    }
+
+    fn context_menu(&self, x: i32, y: i32) {
+        self.record(MockCall::ContextMenu { x, y });
+    }
+
+    fn scroll(&self, delta: i32, orientation: String) {
+        self.record(MockCall::Scroll { delta, orientation });
+    }
+
+    fn provide_xdg_activation_token(&self, token: String) {
+        self.record(MockCall::ProvideXdgActivationToken { token });
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        lock(&self.state).properties.id.clone()
+    }
+
+    #[zbus(property)]
+    fn category(&self) -> String {
+        String::from("ApplicationStatus")
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        lock(&self.state).properties.title.clone()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        lock(&self.state).properties.status.clone()
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        lock(&self.state).properties.icon_name.clone()
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).expect("to be valid object path")
+    }
+}
+
+struct MenuInterface {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl MenuInterface {
+    /// Gets the layout below `parent_id`.
+    /// The whole subtree is always sent, whatever the depth asked for.
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> zbus::fdo::Result<(u32, Layout)> {
+        let mut state = lock(&self.state);
+        state.calls.push(MockCall::GetLayout { parent_id });
+
+        let root = MockMenuItem {
+            id: 0,
+            label: String::new(),
+            submenu: state.properties.menu.clone(),
+        };
+
+        let parent = root.find(parent_id).ok_or_else(|| {
+            zbus::fdo::Error::InvalidArgs(format!("unknown menu item id {parent_id}"))
+        })?;
+
+        let children = parent
+            .submenu
+            .iter()
+            .map(|child| OwnedValue::try_from(Value::Value(Box::new(layout(child)))))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+
+        Ok((state.revision, (parent.id, properties(parent), children)))
+    }
+
+    fn about_to_show(&self, id: i32) -> bool {
+        lock(&self.state).calls.push(MockCall::AboutToShow { id });
+        false
+    }
+
+    fn event(&self, id: i32, event_id: String, _data: Value<'_>, _timestamp: u32) {
+        lock(&self.state)
+            .calls
+            .push(MockCall::MenuEvent { id, event_id });
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> String {
+        String::from("normal")
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> String {
+        String::from("ltr")
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct WatcherInterface {
+    items: Arc<Mutex<Vec<String>>>,
+}
+
+#[interface(name = "org.kde.StatusNotifierWatcher")]
+impl WatcherInterface {
+    async fn register_status_notifier_item(
+        &self,
+        service: String,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> zbus::fdo::Result<()> {
+        // items which pass just their path belong to the connection registering them
+        let service = match header.sender() {
+            Some(sender) if service.starts_with('/') => format!("{sender}{service}"),
+            _ => service,
+        };
+
+        register_item(connection, &self.items, &service).await?;
+        Ok(())
+    }
+
+    async fn unregister_status_notifier_item(
+        &self,
+        service: String,
+        #[zbus(connection)] connection: &Connection,
+    ) -> zbus::fdo::Result<()> {
+        unregister_item(connection, &self.items, &service).await?;
+        Ok(())
+    }
+
+    fn register_status_notifier_host(&self, _service: String) {}
+
+    #[zbus(property)]
+    fn registered_status_notifier_items(&self) -> Vec<String> {
+        lock(&self.items).clone()
+    }
+
+    #[zbus(property)]
+    fn is_status_notifier_host_registered(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn protocol_version(&self) -> i32 {
+        0
+    }
+}
+
+/// Gets the layout of `item` and its submenu, as sent by `GetLayout`.
+fn layout(item: &MockMenuItem) -> Value<'static> {
+    let children = item
+        .submenu
+        .iter()
+        .map(|child| Value::Value(Box::new(layout(child))))
+        .collect::<Vec<_>>();
+
+    Value::from((item.id, properties(item), children))
+}
+
+/// Gets the properties of `item`, as sent by `GetLayout`.
+fn properties(item: &MockMenuItem) -> HashMap<String, OwnedValue> {
+    let mut properties = HashMap::new();
+
+    if !item.label.is_empty() {
+        properties.insert(
+            String::from("label"),
+            OwnedValue::from(Str::from(item.label.clone())),
+        );
+    }
+
+    if !item.submenu.is_empty() {
+        properties.insert(
+            String::from("children-display"),
+            OwnedValue::from(Str::from("submenu")),
+        );
+    }
+
+    properties
+}
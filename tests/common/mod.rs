@@ -0,0 +1,18 @@
+use std::time::Duration;
+use system_tray::client::Event;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::timeout;
+
+/// Waits for the first event matching `predicate`, skipping any others.
+pub async fn wait_for(rx: &mut Receiver<Event>, predicate: impl Fn(&Event) -> bool) -> Event {
+    timeout(Duration::from_secs(5), async {
+        loop {
+            let event = rx.recv().await.expect("client should still be running");
+            if predicate(&event) {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("event should arrive")
+}
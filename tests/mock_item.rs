@@ -0,0 +1,210 @@
+mod common;
+
+use common::wait_for;
+use std::time::Duration;
+use system_tray::client::{ActivateRequest, Event, UpdateEvent};
+use system_tray::test_util::{MockCall, MockItem, MockItemProperties, MockMenuItem, TestBus};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn mock_item_events() {
+    let bus = TestBus::start().expect("bus should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let properties = MockItemProperties {
+        icon_name: String::from("first"),
+        menu: vec![MockMenuItem::new(1, "Options").with_submenu(vec![MockMenuItem::new(2, "Quit")])],
+        ..MockItemProperties::new("mock")
+    };
+    let item = MockItem::start(&bus, properties)
+        .await
+        .expect("item should start");
+    item.register().await.expect("item should register");
+    let address = item.address();
+
+    let event = wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+    let Event::Add(added, added_item) = event else {
+        unreachable!();
+    };
+    assert_eq!(address, added);
+    assert_eq!("mock", added_item.id);
+    assert_eq!(Some("first"), added_item.icon_name.as_deref());
+
+    item.set_icon_name("second")
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, |event| {
+        matches!(event, Event::Update(_, UpdateEvent::Icon(Some(icon))) if icon == "second")
+    })
+    .await;
+
+    client
+        .activate(ActivateRequest::Default {
+            address: address.clone(),
+            x: 1,
+            y: 2,
+        })
+        .await
+        .expect("activate should send");
+    assert_eq!(vec![MockCall::Activate { x: 1, y: 2 }], item.calls());
+
+    let menu = client.open_menu(&address).await.expect("menu should load");
+    assert_eq!(1, menu.submenus.len());
+    assert_eq!(Some("Options"), menu.submenus[0].label.as_deref());
+    assert_eq!(Some("Quit"), menu.submenus[0].submenu[0].label.as_deref());
+
+    item.close().await.expect("connection should close");
+    wait_for(
+        &mut rx,
+        |event| matches!(event, Event::Remove(removed) if *removed == address),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn legacy_signals() {
+    let bus = TestBus::start().expect("bus should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    item.register().await.expect("item should register");
+    let address = item.address();
+    wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+
+    // the mock only sends the `New*` signals, never `PropertiesChanged`
+    item.set_title("title").await.expect("signal should send");
+    wait_for(&mut rx, |event| {
+        matches!(event, Event::Update(updated, UpdateEvent::Title(Some(title)))
+            if *updated == address && title == "title")
+    })
+    .await;
+
+    item.set_icon_name("icon")
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, |event| {
+        matches!(event, Event::Update(updated, UpdateEvent::Icon(Some(icon)))
+            if *updated == address && icon == "icon")
+    })
+    .await;
+
+    let items = client.items();
+    let items = items.lock().expect("state should not be poisoned");
+    let (state_item, _) = items.get(&address).expect("item should be in the state");
+    assert_eq!(Some("title"), state_item.title.as_deref());
+    assert_eq!(Some("icon"), state_item.icon_name.as_deref());
+}
+
+#[tokio::test]
+async fn layout_update_burst() {
+    let bus = TestBus::start().expect("bus should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    item.register().await.expect("item should register");
+    let address = item.address();
+    wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+
+    // opening the menu starts watching it, which is done once an update gets through
+    client.open_menu(&address).await.expect("menu should load");
+    let is_menu = |event: &Event| matches!(event, Event::Update(_, UpdateEvent::Menu(_)));
+    let mut watched = false;
+    for _ in 0..10 {
+        item.set_menu(vec![]).await.expect("signal should send");
+        let update = timeout(Duration::from_millis(500), wait_for(&mut rx, is_menu));
+        if update.await.is_ok() {
+            watched = true;
+            break;
+        }
+    }
+    assert!(watched, "menu should be watched");
+
+    let count_fetches = || {
+        item.calls()
+            .iter()
+            .filter(|call| matches!(call, MockCall::GetLayout { .. }))
+            .count()
+    };
+    let fetched = count_fetches();
+
+    for i in 0..10 {
+        item.set_menu(vec![MockMenuItem::new(1, format!("Item {i}"))])
+            .await
+            .expect("signal should send");
+    }
+
+    wait_for(&mut rx, |event| {
+        matches!(event, Event::Update(_, UpdateEvent::Menu(menu))
+            if menu.submenus.first().and_then(|item| item.label.as_deref()) == Some("Item 9"))
+    })
+    .await;
+
+    // any fetch still to come would have started by now
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let fetches = count_fetches() - fetched;
+    assert!(fetches <= 2, "burst caused {fetches} layout fetches");
+}
+
+#[tokio::test]
+async fn poisoned_state() {
+    let bus = TestBus::start().expect("bus should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    item.register().await.expect("item should register");
+    let address = item.address();
+    wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+
+    // a consumer panicking while holding the state lock poisons it
+    let items = client.items();
+    let poisoned = items.clone();
+    let _ = std::thread::spawn(move || {
+        let _items = poisoned.lock();
+        panic!("poisoning items");
+    })
+    .join();
+    assert!(items.is_poisoned());
+
+    // but the client carries on updating the state and sending events
+    item.set_icon_name("second")
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, |event| {
+        matches!(event, Event::Update(_, UpdateEvent::Icon(Some(icon))) if icon == "second")
+    })
+    .await;
+    assert_eq!(1, client.items_len());
+
+    item.close().await.expect("connection should close");
+    wait_for(
+        &mut rx,
+        |event| matches!(event, Event::Remove(removed) if *removed == address),
+    )
+    .await;
+    assert!(client.items_is_empty());
+}
+
+#[tokio::test]
+async fn subscriber_count() {
+    let bus = TestBus::start().expect("bus should start");
+    let client = bus.client().build().await.expect("client should start");
+    assert_eq!(0, client.subscriber_count());
+
+    let rx = client.subscribe();
+    let bounded = client.subscribe_bounded(1);
+    assert_eq!(2, client.subscriber_count());
+
+    drop(rx);
+    drop(bounded);
+    assert_eq!(0, client.subscriber_count());
+}
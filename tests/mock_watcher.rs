@@ -0,0 +1,225 @@
+mod common;
+
+use common::wait_for;
+use std::time::Duration;
+use system_tray::client::{ActivateRequest, Event, UpdateEvent};
+use system_tray::test_util::{MockCall, MockItem, MockItemProperties, MockWatcher, TestBus};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn mock_watcher_events() {
+    let bus = TestBus::start().expect("bus should start");
+    let mut watcher = MockWatcher::start(&bus)
+        .await
+        .expect("watcher should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    item.register().await.expect("item should register");
+    let address = item.address();
+
+    let is_add = |event: &Event| matches!(event, Event::Add(added, _) if *added == address);
+    let is_remove = |event: &Event| matches!(event, Event::Remove(removed) if *removed == address);
+
+    wait_for(&mut rx, is_add).await;
+    assert_eq!(vec![address.clone()], watcher.items());
+
+    watcher
+        .drop_item(&address)
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, is_remove).await;
+    assert!(client.items_is_empty());
+
+    watcher
+        .register_item(&address)
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, is_add).await;
+
+    watcher.restart().await.expect("watcher should restart");
+    wait_for(&mut rx, is_remove).await;
+}
+
+#[tokio::test]
+async fn duplicate_registration() {
+    let bus = TestBus::start().expect("bus should start");
+    let watcher = MockWatcher::start(&bus)
+        .await
+        .expect("watcher should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    let address = item.address();
+
+    watcher
+        .register_item(&address)
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+
+    // registering again only refreshes the item
+    watcher
+        .register_item(&address)
+        .await
+        .expect("signal should send");
+    let again = timeout(Duration::from_millis(500), async {
+        wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await
+    });
+    assert!(again.await.is_err(), "item should not be added twice");
+    assert_eq!(1, client.items_len());
+
+    // and it is still watched
+    item.set_icon_name("second")
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, |event| {
+        matches!(event, Event::Update(_, UpdateEvent::Icon(Some(icon))) if icon == "second")
+    })
+    .await;
+
+    // registering at another path removes the item, then loads it from there afresh
+    item.serve_at("/org/ayatana/NotificationItem/mock")
+        .await
+        .expect("path should be served");
+    watcher
+        .register_item(&format!("{address}/org/ayatana/NotificationItem/mock"))
+        .await
+        .expect("signal should send");
+    let event = wait_for(&mut rx, |event| {
+        matches!(event, Event::Add(..) | Event::Remove(..))
+    })
+    .await;
+    assert!(matches!(event, Event::Remove(removed) if removed == address));
+    wait_for(
+        &mut rx,
+        |event| matches!(event, Event::Add(added, _) if *added == address),
+    )
+    .await;
+    assert_eq!(1, client.items_len());
+}
+
+#[tokio::test]
+async fn object_path_registration() {
+    let bus = TestBus::start().expect("bus should start");
+    let watcher = MockWatcher::start(&bus)
+        .await
+        .expect("watcher should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    let address = item.address();
+
+    // a bare path cannot be traced back to the item by the client
+    watcher
+        .register_item("/StatusNotifierItem")
+        .await
+        .expect("signal should send");
+
+    // so the watcher adds the name of the connection which registered it
+    item.connection()
+        .call_method(
+            Some("org.kde.StatusNotifierWatcher"),
+            "/StatusNotifierWatcher",
+            Some("org.kde.StatusNotifierWatcher"),
+            "RegisterStatusNotifierItem",
+            &("/StatusNotifierItem",),
+        )
+        .await
+        .expect("item should register");
+
+    let event = wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+    assert!(matches!(event, Event::Add(added, _) if added == address));
+    assert_eq!(1, client.items_len());
+}
+
+#[tokio::test]
+async fn well_known_name_registration() {
+    let bus = TestBus::start().expect("bus should start");
+    let watcher = MockWatcher::start(&bus)
+        .await
+        .expect("watcher should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    let address = item.address();
+    item.connection()
+        .request_name("org.example.App")
+        .await
+        .expect("name should be owned");
+
+    // the item is keyed on the unique name behind the well-known one
+    watcher
+        .register_item("org.example.App")
+        .await
+        .expect("signal should send");
+    let event = wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+    assert!(matches!(event, Event::Add(added, _) if added == address));
+
+    // so it goes away once the connection closes
+    item.close().await.expect("connection should close");
+    wait_for(
+        &mut rx,
+        |event| matches!(event, Event::Remove(removed) if *removed == address),
+    )
+    .await;
+    assert!(client.items_is_empty());
+}
+
+#[tokio::test]
+async fn custom_path_activation() {
+    let bus = TestBus::start().expect("bus should start");
+    let watcher = MockWatcher::start(&bus)
+        .await
+        .expect("watcher should start");
+    let client = bus.client().build().await.expect("client should start");
+    let mut rx = client.subscribe();
+
+    let item = MockItem::start(&bus, MockItemProperties::new("mock"))
+        .await
+        .expect("item should start");
+    let address = item.address();
+    item.move_to("/org/ayatana/NotificationItem/mock")
+        .await
+        .expect("path should be served");
+
+    watcher
+        .register_item(&format!("{address}/org/ayatana/NotificationItem/mock"))
+        .await
+        .expect("signal should send");
+    wait_for(&mut rx, |event| matches!(event, Event::Add(..))).await;
+
+    // calls go to the path the item registered at, including the token
+    client
+        .activate_with_token(
+            ActivateRequest::Default {
+                address: address.clone(),
+                x: 1,
+                y: 2,
+            },
+            "token",
+        )
+        .await
+        .expect("activate should send");
+    assert_eq!(
+        vec![
+            MockCall::ProvideXdgActivationToken {
+                token: String::from("token")
+            },
+            MockCall::Activate { x: 1, y: 2 },
+        ],
+        item.calls()
+    );
+}